@@ -0,0 +1,166 @@
+use log::info;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::read_to_string;
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+
+#[derive(Debug)]
+pub enum AllowlistError {
+    Io(io::Error),
+    InvalidPubkey { line: usize, value: String },
+}
+
+impl fmt::Display for AllowlistError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AllowlistError::Io(e) => write!(f, "error reading allowlist: {}", e),
+            AllowlistError::InvalidPubkey { line, value } => {
+                write!(f, "invalid pubkey on line {}: {:?}", line, value)
+            }
+        }
+    }
+}
+
+/// Pubkeys that are allowed to authenticate with the block engine. The file contains one base58
+/// pubkey per line; blank lines and lines starting with `#` are ignored. Without a file every
+/// pubkey is allowed; with one, no pubkey is allowed until it's loaded.
+#[derive(Clone)]
+pub struct Allowlist {
+    path: Option<PathBuf>,
+    pubkeys: Arc<RwLock<Option<HashSet<Pubkey>>>>,
+}
+
+impl Allowlist {
+    pub fn allow_all() -> Self {
+        Allowlist {
+            path: None,
+            pubkeys: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    /// An allowlist read from the file by [Allowlist::reload]; every pubkey is denied until then.
+    pub fn from_path(path: PathBuf) -> Self {
+        Allowlist {
+            path: Some(path),
+            pubkeys: Arc::new(RwLock::new(Some(HashSet::default()))),
+        }
+    }
+
+    /// Reads the allowlist file, returning the number of allowed pubkeys. The current list is
+    /// kept if the file can't be read. Returns None if every pubkey is allowed.
+    pub fn reload(&self) -> Result<Option<usize>, AllowlistError> {
+        match &self.path {
+            Some(path) => {
+                let pubkeys = Self::read(path)?;
+                let num_pubkeys = pubkeys.len();
+                *self.pubkeys.write().unwrap() = Some(pubkeys);
+                info!("loaded {} pubkeys from allowlist {:?}", num_pubkeys, path);
+                Ok(Some(num_pubkeys))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub fn is_allowed(&self, pubkey: &Pubkey) -> bool {
+        match &*self.pubkeys.read().unwrap() {
+            Some(pubkeys) => pubkeys.contains(pubkey),
+            None => true,
+        }
+    }
+
+    fn read(path: &PathBuf) -> Result<HashSet<Pubkey>, AllowlistError> {
+        let contents = read_to_string(path).map_err(AllowlistError::Io)?;
+        contents
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+            .map(|(line, value)| {
+                Pubkey::from_str(value).map_err(|_| AllowlistError::InvalidPubkey {
+                    line,
+                    value: value.to_string(),
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+    use std::fs::{remove_file, write};
+    use uuid::Uuid;
+
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(contents: &str) -> Self {
+            let path = temp_dir().join(format!("allowlist-{}", Uuid::new_v4()));
+            write(&path, contents).unwrap();
+            TempFile(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_denies_pubkeys_not_listed() {
+        let allowed = Pubkey::new_unique();
+        let file = TempFile::new(&format!("# searchers\n\n  {}  \n", allowed));
+        let allowlist = Allowlist::from_path(file.0.clone());
+        // nothing is allowed before the first load
+        assert!(!allowlist.is_allowed(&allowed));
+
+        assert_eq!(allowlist.reload().unwrap(), Some(1));
+        assert!(allowlist.is_allowed(&allowed));
+        assert!(!allowlist.is_allowed(&Pubkey::new_unique()));
+    }
+
+    #[test]
+    fn test_reload_replaces_allowed_set() {
+        let (first, second) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let file = TempFile::new(&first.to_string());
+        let allowlist = Allowlist::from_path(file.0.clone());
+        allowlist.reload().unwrap();
+        assert!(allowlist.is_allowed(&first));
+
+        write(&file.0, second.to_string()).unwrap();
+        assert_eq!(allowlist.reload().unwrap(), Some(1));
+        assert!(!allowlist.is_allowed(&first));
+        assert!(allowlist.is_allowed(&second));
+    }
+
+    #[test]
+    fn test_invalid_file_keeps_current_set() {
+        let allowed = Pubkey::new_unique();
+        let file = TempFile::new(&allowed.to_string());
+        let allowlist = Allowlist::from_path(file.0.clone());
+        allowlist.reload().unwrap();
+
+        write(&file.0, format!("{}\nnot-a-pubkey\n", Pubkey::new_unique())).unwrap();
+        match allowlist.reload() {
+            Err(AllowlistError::InvalidPubkey { line, value }) => {
+                assert_eq!(line, 2);
+                assert_eq!(value, "not-a-pubkey");
+            }
+            result => panic!("expected an invalid pubkey, got {:?}", result),
+        }
+        assert!(allowlist.is_allowed(&allowed));
+    }
+
+    #[test]
+    fn test_allow_all() {
+        let allowlist = Allowlist::allow_all();
+        assert_eq!(allowlist.reload().unwrap(), None);
+        assert!(allowlist.is_allowed(&Pubkey::new_unique()));
+    }
+}
@@ -0,0 +1,69 @@
+use crate::allowlist::Allowlist;
+use crate::metrics::AUTH_FAILURES;
+use crate::token::{AuthenticatedIdentity, TokenStore};
+use tonic::service::Interceptor;
+use tonic::{Request, Status};
+
+/// Resolves the `authorization: Bearer <access token>` header to an [AuthenticatedIdentity] and
+/// stores it in the request extensions. Requests without the header are passed through
+/// anonymously unless `require_auth` is set.
+#[derive(Clone)]
+pub struct AuthInterceptor {
+    token_store: TokenStore,
+    allowlist: Allowlist,
+    require_auth: bool,
+}
+
+impl AuthInterceptor {
+    pub fn new(token_store: TokenStore, allowlist: Allowlist, require_auth: bool) -> Self {
+        AuthInterceptor {
+            token_store,
+            allowlist,
+            require_auth,
+        }
+    }
+}
+
+impl Interceptor for AuthInterceptor {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        let header = match request.metadata().get("authorization") {
+            Some(header) => header,
+            None if self.require_auth => {
+                AUTH_FAILURES
+                    .with_label_values(&["access_token", "missing_token"])
+                    .inc();
+                return Err(Status::unauthenticated("missing authorization header"));
+            }
+            None => return Ok(request),
+        };
+
+        let identity = header
+            .to_str()
+            .ok()
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .and_then(|token| self.token_store.validate_access_token(token))
+            .ok_or_else(|| {
+                AUTH_FAILURES
+                    .with_label_values(&["access_token", "invalid_token"])
+                    .inc();
+                Status::unauthenticated("invalid or expired access token")
+            })?;
+
+        // pubkeys removed from the allowlist lose access without waiting for their tokens to
+        // expire
+        if !self.allowlist.is_allowed(&identity.pubkey) {
+            AUTH_FAILURES
+                .with_label_values(&["access_token", "not_allowed"])
+                .inc();
+            return Err(Status::permission_denied("pubkey is not allowed"));
+        }
+
+        request.extensions_mut().insert(identity);
+        Ok(request)
+    }
+}
+
+/// Returns the identity the [AuthInterceptor] resolved for the request, if any.
+pub fn authenticated_identity<T>(request: &Request<T>) -> Option<AuthenticatedIdentity> {
+    request.extensions().get::<AuthenticatedIdentity>().copied()
+}
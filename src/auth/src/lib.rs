@@ -1 +1,5 @@
+pub mod allowlist;
+pub mod interceptor;
+pub mod metrics;
 pub mod server;
+pub mod token;
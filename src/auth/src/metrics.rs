@@ -0,0 +1,20 @@
+use lazy_static::lazy_static;
+use prometheus::{register_int_counter_vec, IntCounterVec};
+
+lazy_static! {
+    /// Successful auth requests, by method.
+    pub static ref AUTH_SUCCESSES: IntCounterVec = register_int_counter_vec!(
+        "block_engine_auth_successes_total",
+        "Successful auth requests",
+        &["method"]
+    )
+    .unwrap();
+
+    /// Failed auth requests, by method and reason.
+    pub static ref AUTH_FAILURES: IntCounterVec = register_int_counter_vec!(
+        "block_engine_auth_failures_total",
+        "Failed auth requests",
+        &["method", "reason"]
+    )
+    .unwrap();
+}
@@ -1,17 +1,47 @@
+use crate::allowlist::Allowlist;
+use crate::metrics::{AUTH_FAILURES, AUTH_SUCCESSES};
+use crate::token::{AuthenticatedIdentity, TokenStore};
 use jito_protos::auth::{
     auth_service_server::AuthService, GenerateAuthChallengeRequest, GenerateAuthChallengeResponse,
     GenerateAuthTokensRequest, GenerateAuthTokensResponse, RefreshAccessTokenRequest,
-    RefreshAccessTokenResponse, Token as PbToken,
+    RefreshAccessTokenResponse, Role,
 };
 use log::*;
-use std::ops::Add;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
 use tonic::{Request, Response, Status};
-pub struct AuthServiceImpl {}
+
+pub struct AuthServiceImpl {
+    token_store: TokenStore,
+    allowlist: Allowlist,
+}
 
 impl AuthServiceImpl {
-    pub fn new() -> Self {
-        AuthServiceImpl {}
+    pub fn new(token_store: TokenStore, allowlist: Allowlist) -> Self {
+        AuthServiceImpl {
+            token_store,
+            allowlist,
+        }
+    }
+
+    fn parse_pubkey(method: &str, bytes: &[u8]) -> Result<Pubkey, Status> {
+        if bytes.len() != 32 {
+            AUTH_FAILURES
+                .with_label_values(&[method, "invalid_pubkey"])
+                .inc();
+            return Err(Status::invalid_argument("pubkey must be 32 bytes"));
+        }
+        Ok(Pubkey::new(bytes))
+    }
+
+    fn check_allowed(&self, method: &str, pubkey: &Pubkey) -> Result<(), Status> {
+        if !self.allowlist.is_allowed(pubkey) {
+            AUTH_FAILURES
+                .with_label_values(&[method, "not_allowed"])
+                .inc();
+            return Err(Status::permission_denied("pubkey is not allowed"));
+        }
+        Ok(())
     }
 }
 
@@ -19,61 +49,102 @@ impl AuthServiceImpl {
 impl AuthService for AuthServiceImpl {
     async fn generate_auth_challenge(
         &self,
-        _req: Request<GenerateAuthChallengeRequest>,
+        req: Request<GenerateAuthChallengeRequest>,
     ) -> Result<Response<GenerateAuthChallengeResponse>, Status> {
-        info!("generate_auth_challenge");
-        Ok(Response::new(GenerateAuthChallengeResponse {
-            challenge: "generate_auth_challenge".into(),
-        }))
+        const METHOD: &str = "generate_auth_challenge";
+
+        let req = req.into_inner();
+        let pubkey = Self::parse_pubkey(METHOD, &req.pubkey)?;
+        let role = Role::from_i32(req.role).ok_or_else(|| {
+            AUTH_FAILURES
+                .with_label_values(&[METHOD, "invalid_role"])
+                .inc();
+            Status::invalid_argument("unknown role")
+        })?;
+        self.check_allowed(METHOD, &pubkey)?;
+
+        info!(
+            "generate_auth_challenge pubkey: {} role: {:?}",
+            pubkey, role
+        );
+        let challenge = self
+            .token_store
+            .new_challenge(pubkey, role)
+            .ok_or_else(|| {
+                AUTH_FAILURES
+                    .with_label_values(&[METHOD, "too_many_challenges"])
+                    .inc();
+                Status::resource_exhausted("too many outstanding challenges, try again later")
+            })?;
+        AUTH_SUCCESSES.with_label_values(&[METHOD]).inc();
+        Ok(Response::new(GenerateAuthChallengeResponse { challenge }))
     }
 
     async fn generate_auth_tokens(
         &self,
-        _req: Request<GenerateAuthTokensRequest>,
+        req: Request<GenerateAuthTokensRequest>,
     ) -> Result<Response<GenerateAuthTokensResponse>, Status> {
-        info!("generate_auth_tokens");
+        const METHOD: &str = "generate_auth_tokens";
+
+        let req = req.into_inner();
+        let pubkey = Self::parse_pubkey(METHOD, &req.client_pubkey)?;
+        self.check_allowed(METHOD, &pubkey)?;
 
-        let expiration_time = SystemTime::now()
-            .add(Duration::from_secs(24 * 60 * 60))
-            .duration_since(UNIX_EPOCH)
-            .expect("expiration time calc");
+        // clients sign "<pubkey>-<challenge>". Checked before the challenge is taken, so only the
+        // key holder can use it up.
+        let message = format!("{}-{}", pubkey, req.challenge);
+        if req.signed_challenge.len() != 64
+            || !Signature::new(&req.signed_challenge).verify(pubkey.as_ref(), message.as_bytes())
+        {
+            AUTH_FAILURES
+                .with_label_values(&[METHOD, "invalid_signature"])
+                .inc();
+            return Err(Status::permission_denied("invalid challenge signature"));
+        }
 
+        let role = self
+            .token_store
+            .take_challenge(&pubkey, &req.challenge)
+            .ok_or_else(|| {
+                AUTH_FAILURES
+                    .with_label_values(&[METHOD, "invalid_challenge"])
+                    .inc();
+                Status::permission_denied("invalid or expired challenge")
+            })?;
+
+        info!("generate_auth_tokens pubkey: {} role: {:?}", pubkey, role);
+        let (access_token, refresh_token) = self
+            .token_store
+            .issue_tokens(AuthenticatedIdentity { pubkey, role });
+
+        AUTH_SUCCESSES.with_label_values(&[METHOD]).inc();
         Ok(Response::new(GenerateAuthTokensResponse {
-            access_token: Some(PbToken {
-                value: "access_token".into(),
-                expires_at_utc: Some(prost_types::Timestamp {
-                    seconds: expiration_time.as_secs() as i64,
-                    nanos: 0,
-                }),
-            }),
-            refresh_token: Some(PbToken {
-                value: "refresh_token".into(),
-                expires_at_utc: Some(prost_types::Timestamp {
-                    seconds: expiration_time.as_secs() as i64,
-                    nanos: 0,
-                }),
-            }),
+            access_token: Some(access_token),
+            refresh_token: Some(refresh_token),
         }))
     }
 
     async fn refresh_access_token(
         &self,
-        _req: Request<RefreshAccessTokenRequest>,
+        req: Request<RefreshAccessTokenRequest>,
     ) -> Result<Response<RefreshAccessTokenResponse>, Status> {
-        info!("refresh_access_token");
+        const METHOD: &str = "refresh_access_token";
+
+        let (identity, access_token) = self
+            .token_store
+            .refresh_access_token(&req.into_inner().refresh_token)
+            .ok_or_else(|| {
+                AUTH_FAILURES
+                    .with_label_values(&[METHOD, "invalid_token"])
+                    .inc();
+                Status::unauthenticated("invalid or expired refresh token")
+            })?;
+        self.check_allowed(METHOD, &identity.pubkey)?;
 
-        let expiration_time = SystemTime::now()
-            .add(Duration::from_secs(30 * 60))
-            .duration_since(UNIX_EPOCH)
-            .expect("expiration time calc");
+        info!("refresh_access_token pubkey: {}", identity.pubkey);
+        AUTH_SUCCESSES.with_label_values(&[METHOD]).inc();
         Ok(Response::new(RefreshAccessTokenResponse {
-            access_token: Some(PbToken {
-                value: "access_token".into(),
-                expires_at_utc: Some(prost_types::Timestamp {
-                    seconds: expiration_time.as_secs() as i64,
-                    nanos: 0,
-                }),
-            }),
+            access_token: Some(access_token),
         }))
     }
 }
@@ -0,0 +1,270 @@
+use jito_protos::auth::{Role, Token as PbToken};
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::ops::Add;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+/// The pubkey and role a token or challenge was issued to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AuthenticatedIdentity {
+    pub pubkey: Pubkey,
+    pub role: Role,
+}
+
+struct Issued<T> {
+    value: T,
+    expires_at: SystemTime,
+}
+
+impl<T> Issued<T> {
+    fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires_at <= now
+    }
+}
+
+#[derive(Default)]
+struct TokenStoreInner {
+    challenges: HashMap<Pubkey, Issued<(String, Role)>>,
+    access_tokens: HashMap<String, Issued<AuthenticatedIdentity>>,
+    refresh_tokens: HashMap<String, Issued<AuthenticatedIdentity>>,
+}
+
+/// Issues challenges and tokens and resolves tokens back to the identity they were issued to.
+#[derive(Clone, Default)]
+pub struct TokenStore {
+    inner: Arc<Mutex<TokenStoreInner>>,
+}
+
+impl TokenStore {
+    pub const CHALLENGE_TTL: Duration = Duration::from_secs(60);
+    pub const ACCESS_TOKEN_TTL: Duration = Duration::from_secs(30 * 60);
+    pub const REFRESH_TOKEN_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+    /// Outstanding challenges allowed at once. Challenges are handed out before the caller has
+    /// proven anything, so this bounds what they can hold for [TokenStore::CHALLENGE_TTL].
+    pub const MAX_CHALLENGES: usize = 10_000;
+
+    /// Creates a challenge for the pubkey, replacing any outstanding one. Returns None if
+    /// [TokenStore::MAX_CHALLENGES] other pubkeys have one outstanding.
+    pub fn new_challenge(&self, pubkey: Pubkey, role: Role) -> Option<String> {
+        let challenge = Uuid::new_v4().to_string();
+        let mut inner = self.inner.lock().unwrap();
+        Self::prune(&mut inner);
+        if inner.challenges.len() >= Self::MAX_CHALLENGES && !inner.challenges.contains_key(&pubkey)
+        {
+            return None;
+        }
+        inner.challenges.insert(
+            pubkey,
+            Issued {
+                value: (challenge.clone(), role),
+                expires_at: SystemTime::now().add(Self::CHALLENGE_TTL),
+            },
+        );
+        Some(challenge)
+    }
+
+    /// Removes the pubkey's outstanding challenge and returns the role it was issued for, if it
+    /// matches `challenge` and hasn't expired. A mismatched challenge is left in place so a bad
+    /// attempt doesn't void the real one.
+    pub fn take_challenge(&self, pubkey: &Pubkey, challenge: &str) -> Option<Role> {
+        let mut inner = self.inner.lock().unwrap();
+        let issued = inner.challenges.get(pubkey)?;
+        if issued.is_expired(SystemTime::now()) || issued.value.0 != challenge {
+            return None;
+        }
+        inner.challenges.remove(pubkey).map(|issued| issued.value.1)
+    }
+
+    /// Issues an access token and a refresh token for the identity.
+    pub fn issue_tokens(&self, identity: AuthenticatedIdentity) -> (PbToken, PbToken) {
+        let mut inner = self.inner.lock().unwrap();
+        Self::prune(&mut inner);
+        let access_token = Self::issue(&mut inner.access_tokens, identity, Self::ACCESS_TOKEN_TTL);
+        let refresh_token =
+            Self::issue(&mut inner.refresh_tokens, identity, Self::REFRESH_TOKEN_TTL);
+        (access_token, refresh_token)
+    }
+
+    /// Issues a new access token for the identity holding the refresh token.
+    pub fn refresh_access_token(
+        &self,
+        refresh_token: &str,
+    ) -> Option<(AuthenticatedIdentity, PbToken)> {
+        let mut inner = self.inner.lock().unwrap();
+        Self::prune(&mut inner);
+        let identity = Self::lookup(&inner.refresh_tokens, refresh_token)?;
+        let access_token = Self::issue(&mut inner.access_tokens, identity, Self::ACCESS_TOKEN_TTL);
+        Some((identity, access_token))
+    }
+
+    pub fn validate_access_token(&self, access_token: &str) -> Option<AuthenticatedIdentity> {
+        Self::lookup(&self.inner.lock().unwrap().access_tokens, access_token)
+    }
+
+    fn lookup(
+        tokens: &HashMap<String, Issued<AuthenticatedIdentity>>,
+        token: &str,
+    ) -> Option<AuthenticatedIdentity> {
+        tokens
+            .get(token)
+            .filter(|issued| !issued.is_expired(SystemTime::now()))
+            .map(|issued| issued.value)
+    }
+
+    fn issue(
+        tokens: &mut HashMap<String, Issued<AuthenticatedIdentity>>,
+        identity: AuthenticatedIdentity,
+        ttl: Duration,
+    ) -> PbToken {
+        let value = Uuid::new_v4().to_string();
+        let expires_at = SystemTime::now().add(ttl);
+        tokens.insert(
+            value.clone(),
+            Issued {
+                value: identity,
+                expires_at,
+            },
+        );
+
+        let expires_at = expires_at
+            .duration_since(UNIX_EPOCH)
+            .expect("expiration time calc");
+        PbToken {
+            value,
+            expires_at_utc: Some(prost_types::Timestamp {
+                seconds: expires_at.as_secs() as i64,
+                nanos: 0,
+            }),
+        }
+    }
+
+    /// Drops expired challenges and tokens. Runs whenever one is issued, so clients that only
+    /// refresh don't grow the store without bound.
+    fn prune(inner: &mut TokenStoreInner) {
+        let now = SystemTime::now();
+        inner.challenges.retain(|_, issued| !issued.is_expired(now));
+        inner
+            .access_tokens
+            .retain(|_, issued| !issued.is_expired(now));
+        inner
+            .refresh_tokens
+            .retain(|_, issued| !issued.is_expired(now));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity() -> AuthenticatedIdentity {
+        AuthenticatedIdentity {
+            pubkey: Pubkey::new_unique(),
+            role: Role::Searcher,
+        }
+    }
+
+    #[test]
+    fn test_take_challenge_requires_match() {
+        let token_store = TokenStore::default();
+        let pubkey = Pubkey::new_unique();
+        let challenge = token_store.new_challenge(pubkey, Role::Validator).unwrap();
+
+        // a mismatched challenge is rejected and leaves the outstanding one usable
+        assert_eq!(
+            token_store.take_challenge(&pubkey, "not-the-challenge"),
+            None
+        );
+        assert_eq!(
+            token_store.take_challenge(&Pubkey::new_unique(), &challenge),
+            None
+        );
+        assert_eq!(
+            token_store.take_challenge(&pubkey, &challenge),
+            Some(Role::Validator)
+        );
+        // and it can only be used once
+        assert_eq!(token_store.take_challenge(&pubkey, &challenge), None);
+    }
+
+    #[test]
+    fn test_take_challenge_rejects_expired() {
+        let token_store = TokenStore::default();
+        let pubkey = Pubkey::new_unique();
+        let challenge = token_store.new_challenge(pubkey, Role::Searcher).unwrap();
+        token_store
+            .inner
+            .lock()
+            .unwrap()
+            .challenges
+            .get_mut(&pubkey)
+            .unwrap()
+            .expires_at = SystemTime::now();
+        assert_eq!(token_store.take_challenge(&pubkey, &challenge), None);
+    }
+
+    #[test]
+    fn test_new_challenge_is_capped() {
+        let token_store = TokenStore::default();
+        let pubkeys: Vec<_> = (0..TokenStore::MAX_CHALLENGES)
+            .map(|_| Pubkey::new_unique())
+            .collect();
+        for pubkey in &pubkeys {
+            assert!(token_store.new_challenge(*pubkey, Role::Searcher).is_some());
+        }
+        assert_eq!(
+            token_store.new_challenge(Pubkey::new_unique(), Role::Searcher),
+            None
+        );
+        // pubkeys that already have a challenge can still replace it
+        assert!(token_store
+            .new_challenge(pubkeys[0], Role::Searcher)
+            .is_some());
+    }
+
+    #[test]
+    fn test_expired_tokens_are_rejected() {
+        let token_store = TokenStore::default();
+        let identity = identity();
+        let (access_token, refresh_token) = token_store.issue_tokens(identity);
+        assert_eq!(
+            token_store.validate_access_token(&access_token.value),
+            Some(identity)
+        );
+
+        {
+            let mut inner = token_store.inner.lock().unwrap();
+            let now = SystemTime::now();
+            inner
+                .access_tokens
+                .get_mut(&access_token.value)
+                .unwrap()
+                .expires_at = now;
+            inner
+                .refresh_tokens
+                .get_mut(&refresh_token.value)
+                .unwrap()
+                .expires_at = now;
+        }
+        assert_eq!(token_store.validate_access_token(&access_token.value), None);
+        assert!(token_store
+            .refresh_access_token(&refresh_token.value)
+            .is_none());
+    }
+
+    #[test]
+    fn test_refresh_issues_access_token_for_same_identity() {
+        let token_store = TokenStore::default();
+        let identity = identity();
+        let (_, refresh_token) = token_store.issue_tokens(identity);
+        let (refreshed, access_token) = token_store
+            .refresh_access_token(&refresh_token.value)
+            .unwrap();
+        assert_eq!(refreshed, identity);
+        assert_eq!(
+            token_store.validate_access_token(&access_token.value),
+            Some(identity)
+        );
+    }
+}
@@ -0,0 +1,394 @@
+use hyper::header::CONTENT_TYPE;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use jito_auth::allowlist::Allowlist;
+use jito_searcher::connected_searchers::ConnectedSearchers;
+use jito_searcher::rate_limiter::{RateLimitKey, RateLimiter};
+use jito_validator::backpressure::BackpressurePolicy;
+use jito_validator::forwarder::{BundleEventKind, ForwardedBundle, Forwarder};
+use jito_validator::packet_filter::PacketFilter;
+use jito_validator::routing::RoutingMode;
+use log::info;
+use serde_json::{json, Value};
+use solana_sdk::pubkey::Pubkey;
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+/// Handles into the running block engine that operators can inspect and control.
+pub struct AdminState {
+    pub forwarder: Forwarder,
+    pub connected_searchers: ConnectedSearchers,
+    pub rate_limiter: Option<RateLimiter>,
+    pub allowlist: Allowlist,
+}
+
+/// Serves the admin JSON API until `shutdown` resolves:
+/// - `GET /validators`: validator subscriptions with identity and age
+/// - `DELETE /subscriptions/<uuid>`: closes a validator subscription
+/// - `GET /searchers`: searchers seen recently, with their rate limit usage
+/// - `GET /bundles`: recently forwarded bundles
+/// - `GET /bundles/<uuid>`: a recently forwarded bundle and its lifecycle events
+/// - `GET /routing-mode`, `PUT /routing-mode` with `{"mode": "broadcast" | "leader_only"}`
+/// - `GET /backpressure`, `PUT /backpressure` with `{"policy": <policy>, "identity": <pubkey>}`
+///   (identity optional, sets the default if omitted)
+/// - `DELETE /backpressure/<pubkey>`: removes a validator's backpressure override
+/// - `GET /packet-filters`, `PUT /packet-filters` with
+///   `{"identity": <pubkey>, "leaders_only": bool, "drop_votes": bool, "sample_percent": 0-100}`
+///   (identity optional, sets the default if omitted; omitted fields don't filter)
+/// - `DELETE /packet-filters/<pubkey>`: removes a validator's packet filter override
+/// - `POST /auth/reload`: re-reads the auth allowlist
+pub async fn serve_admin(
+    addr: SocketAddr,
+    state: AdminState,
+    shutdown: impl Future<Output = ()>,
+) -> Result<(), hyper::Error> {
+    let state = Arc::new(state);
+    let make_service = make_service_fn(move |_| {
+        let state = state.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                handle_request(request, state.clone())
+            }))
+        }
+    });
+    Server::try_bind(&addr)?
+        .serve(make_service)
+        .with_graceful_shutdown(shutdown)
+        .await
+}
+
+async fn handle_request(
+    request: Request<Body>,
+    state: Arc<AdminState>,
+) -> Result<Response<Body>, Infallible> {
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+    let response = match (&method, segments.as_slice()) {
+        (&Method::GET, ["validators"]) => list_validators(&state),
+        (&Method::DELETE, ["subscriptions", uuid]) => disconnect(&state, uuid),
+        (&Method::GET, ["searchers"]) => list_searchers(&state),
+        (&Method::GET, ["bundles"]) => recent_bundles(&state),
+        (&Method::GET, ["bundles", uuid]) => bundle(&state, uuid),
+        (&Method::GET, ["routing-mode"]) => json_response(
+            StatusCode::OK,
+            json!({ "mode": state.forwarder.routing_mode().to_string() }),
+        ),
+        (&Method::PUT, ["routing-mode"]) => set_routing_mode(&state, request).await,
+        (&Method::GET, ["backpressure"]) => backpressure_policies(&state),
+        (&Method::PUT, ["backpressure"]) => set_backpressure_policy(&state, request).await,
+        (&Method::DELETE, ["backpressure", identity]) => {
+            clear_backpressure_policy(&state, identity)
+        }
+        (&Method::GET, ["packet-filters"]) => packet_filters(&state),
+        (&Method::PUT, ["packet-filters"]) => set_packet_filter(&state, request).await,
+        (&Method::DELETE, ["packet-filters", identity]) => clear_packet_filter(&state, identity),
+        (&Method::POST, ["auth", "reload"]) => reload_allowlist(&state),
+        _ => error_response(StatusCode::NOT_FOUND, "not found"),
+    };
+    Ok(response)
+}
+
+fn list_validators(state: &AdminState) -> Response<Body> {
+    let subscriptions: Vec<Value> = state
+        .forwarder
+        .subscriptions()
+        .into_iter()
+        .map(|sub| {
+            json!({
+                "uuid": sub.uuid.to_string(),
+                "stream": sub.stream,
+                "identity": sub.identity.map(|identity| identity.to_string()),
+                "age_secs": sub.age.as_secs(),
+                "backpressure_policy": sub.backpressure_policy.to_string(),
+            })
+        })
+        .collect();
+    json_response(StatusCode::OK, json!(subscriptions))
+}
+
+fn disconnect(state: &AdminState, uuid: &str) -> Response<Body> {
+    let uuid = match Uuid::from_str(uuid) {
+        Ok(uuid) => uuid,
+        Err(_) => return error_response(StatusCode::BAD_REQUEST, "invalid uuid"),
+    };
+    if state.forwarder.disconnect(&uuid) {
+        info!("admin disconnected subscription uuid: {:?}", uuid);
+        json_response(StatusCode::OK, json!({ "disconnected": uuid.to_string() }))
+    } else {
+        error_response(StatusCode::NOT_FOUND, "no subscription with that uuid")
+    }
+}
+
+fn list_searchers(state: &AdminState) -> Response<Body> {
+    let searchers: Vec<Value> = state
+        .connected_searchers
+        .list()
+        .into_iter()
+        .map(|searcher| {
+            let rate_limit_usage = state.rate_limiter.as_ref().map(|rate_limiter| {
+                let key = match searcher.identity {
+                    Some(identity) => RateLimitKey::Pubkey(identity),
+                    None => RateLimitKey::Ip(searcher.addr.ip()),
+                };
+                rate_limiter.usage(&key)
+            });
+            json!({
+                "addr": searcher.addr.to_string(),
+                "identity": searcher.identity.map(|identity| identity.to_string()),
+                "age_secs": searcher.first_seen.elapsed().as_secs(),
+                "idle_secs": searcher.last_seen.elapsed().as_secs(),
+                "rate_limit_usage": rate_limit_usage,
+            })
+        })
+        .collect();
+    json_response(StatusCode::OK, json!(searchers))
+}
+
+fn recent_bundles(state: &AdminState) -> Response<Body> {
+    let bundles = state.forwarder.recent_bundles();
+    let validators_sent: usize = bundles.iter().map(|b| b.validators_sent).sum();
+    let validators_delivered: usize = bundles.iter().map(|b| b.validators_delivered).sum();
+    let validators_dropped: usize = bundles.iter().map(|b| b.validators_dropped).sum();
+    let bundles: Vec<Value> = bundles.iter().map(forwarded_bundle_json).collect();
+    json_response(
+        StatusCode::OK,
+        json!({
+            "num_bundles": bundles.len(),
+            "validators_sent": validators_sent,
+            "validators_delivered": validators_delivered,
+            "validators_dropped": validators_dropped,
+            "bundles": bundles,
+        }),
+    )
+}
+
+fn bundle(state: &AdminState, uuid: &str) -> Response<Body> {
+    let bundle = match state.forwarder.bundle(uuid) {
+        Some(bundle) => bundle,
+        None => return error_response(StatusCode::NOT_FOUND, "no recent bundle with that uuid"),
+    };
+
+    let events: Vec<Value> = bundle
+        .events
+        .iter()
+        .map(|event| {
+            let mut value = json!({
+                "at_ms": unix_millis(event.at),
+                "subscription": event.subscription.to_string(),
+                "identity": event.identity.map(|identity| identity.to_string()),
+            });
+            match &event.kind {
+                BundleEventKind::Delivered => value["event"] = json!("delivered"),
+                BundleEventKind::Dropped { reason } => {
+                    value["event"] = json!("dropped");
+                    value["reason"] = json!(reason);
+                }
+                BundleEventKind::Retried => value["event"] = json!("retried"),
+            }
+            value
+        })
+        .collect();
+    let mut value = forwarded_bundle_json(&bundle);
+    value["events"] = json!(events);
+    json_response(StatusCode::OK, value)
+}
+
+fn forwarded_bundle_json(bundle: &ForwardedBundle) -> Value {
+    json!({
+        "uuid": bundle.uuid,
+        "forwarded_at_ms": unix_millis(bundle.forwarded_at),
+        "num_packets": bundle.num_packets,
+        "validators_sent": bundle.validators_sent,
+        "validators_delivered": bundle.validators_delivered,
+        "validators_dropped": bundle.validators_dropped,
+    })
+}
+
+fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |t| t.as_millis() as u64)
+}
+
+async fn set_routing_mode(state: &AdminState, request: Request<Body>) -> Response<Body> {
+    let body = match hyper::body::to_bytes(request.into_body()).await {
+        Ok(body) => body,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, &e.to_string()),
+    };
+    let routing_mode = serde_json::from_slice::<Value>(&body)
+        .ok()
+        .and_then(|body| body.get("mode")?.as_str().map(RoutingMode::from_str));
+    let routing_mode = match routing_mode {
+        Some(Ok(routing_mode)) => routing_mode,
+        Some(Err(e)) => return error_response(StatusCode::BAD_REQUEST, &e),
+        None => return error_response(StatusCode::BAD_REQUEST, "expected {\"mode\": <mode>}"),
+    };
+
+    match state.forwarder.set_routing_mode(routing_mode) {
+        Ok(()) => json_response(StatusCode::OK, json!({ "mode": routing_mode.to_string() })),
+        Err(e) => error_response(StatusCode::CONFLICT, &e),
+    }
+}
+
+fn backpressure_policies(state: &AdminState) -> Response<Body> {
+    let policies = state.forwarder.backpressure_policies();
+    let overrides: serde_json::Map<String, Value> = policies
+        .overrides
+        .iter()
+        .map(|(identity, policy)| (identity.to_string(), json!(policy.to_string())))
+        .collect();
+    json_response(
+        StatusCode::OK,
+        json!({ "default": policies.default.to_string(), "overrides": overrides }),
+    )
+}
+
+async fn set_backpressure_policy(state: &AdminState, request: Request<Body>) -> Response<Body> {
+    let body = match hyper::body::to_bytes(request.into_body()).await {
+        Ok(body) => body,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, &e.to_string()),
+    };
+    let body = match serde_json::from_slice::<Value>(&body) {
+        Ok(body) => body,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, &e.to_string()),
+    };
+    let policy = match body.get("policy").and_then(Value::as_str) {
+        Some(policy) => match BackpressurePolicy::from_str(policy) {
+            Ok(policy) => policy,
+            Err(e) => return error_response(StatusCode::BAD_REQUEST, &e),
+        },
+        None => return error_response(StatusCode::BAD_REQUEST, "expected {\"policy\": <policy>}"),
+    };
+    let identity = match body.get("identity").and_then(Value::as_str) {
+        Some(identity) => match Pubkey::from_str(identity) {
+            Ok(identity) => Some(identity),
+            Err(_) => return error_response(StatusCode::BAD_REQUEST, "invalid identity"),
+        },
+        None => None,
+    };
+
+    state.forwarder.set_backpressure_policy(identity, policy);
+    json_response(
+        StatusCode::OK,
+        json!({
+            "identity": identity.map(|identity| identity.to_string()),
+            "policy": policy.to_string(),
+        }),
+    )
+}
+
+fn clear_backpressure_policy(state: &AdminState, identity: &str) -> Response<Body> {
+    let identity = match Pubkey::from_str(identity) {
+        Ok(identity) => identity,
+        Err(_) => return error_response(StatusCode::BAD_REQUEST, "invalid identity"),
+    };
+    if state.forwarder.clear_backpressure_policy(&identity) {
+        json_response(StatusCode::OK, json!({ "cleared": identity.to_string() }))
+    } else {
+        error_response(StatusCode::NOT_FOUND, "no override for that identity")
+    }
+}
+
+fn packet_filters(state: &AdminState) -> Response<Body> {
+    let filters = state.forwarder.packet_filters();
+    let overrides: serde_json::Map<String, Value> = filters
+        .overrides
+        .iter()
+        .map(|(identity, filter)| (identity.to_string(), packet_filter_json(filter)))
+        .collect();
+    json_response(
+        StatusCode::OK,
+        json!({ "default": packet_filter_json(&filters.default), "overrides": overrides }),
+    )
+}
+
+async fn set_packet_filter(state: &AdminState, request: Request<Body>) -> Response<Body> {
+    let body = match hyper::body::to_bytes(request.into_body()).await {
+        Ok(body) => body,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, &e.to_string()),
+    };
+    let body = match serde_json::from_slice::<Value>(&body) {
+        Ok(body) => body,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, &e.to_string()),
+    };
+    let identity = match body.get("identity").and_then(Value::as_str) {
+        Some(identity) => match Pubkey::from_str(identity) {
+            Ok(identity) => Some(identity),
+            Err(_) => return error_response(StatusCode::BAD_REQUEST, "invalid identity"),
+        },
+        None => None,
+    };
+    let sample_percent = match body.get("sample_percent") {
+        None => 100,
+        Some(value) => match value.as_u64() {
+            Some(sample_percent) if sample_percent <= 100 => sample_percent as u8,
+            _ => {
+                return error_response(
+                    StatusCode::BAD_REQUEST,
+                    "sample_percent must be an integer from 0 to 100",
+                )
+            }
+        },
+    };
+    let filter = PacketFilter {
+        leaders_only: body
+            .get("leaders_only")
+            .and_then(Value::as_bool)
+            .unwrap_or(false),
+        drop_votes: body
+            .get("drop_votes")
+            .and_then(Value::as_bool)
+            .unwrap_or(false),
+        sample_percent,
+    };
+
+    match state.forwarder.set_packet_filter(identity, filter) {
+        Ok(()) => json_response(StatusCode::OK, packet_filter_json(&filter)),
+        Err(e) => error_response(StatusCode::CONFLICT, &e),
+    }
+}
+
+fn clear_packet_filter(state: &AdminState, identity: &str) -> Response<Body> {
+    let identity = match Pubkey::from_str(identity) {
+        Ok(identity) => identity,
+        Err(_) => return error_response(StatusCode::BAD_REQUEST, "invalid identity"),
+    };
+    if state.forwarder.clear_packet_filter(&identity) {
+        json_response(StatusCode::OK, json!({ "cleared": identity.to_string() }))
+    } else {
+        error_response(StatusCode::NOT_FOUND, "no override for that identity")
+    }
+}
+
+fn packet_filter_json(filter: &PacketFilter) -> Value {
+    json!({
+        "leaders_only": filter.leaders_only,
+        "drop_votes": filter.drop_votes,
+        "sample_percent": filter.sample_percent,
+    })
+}
+
+fn reload_allowlist(state: &AdminState) -> Response<Body> {
+    match state.allowlist.reload() {
+        Ok(num_pubkeys) => json_response(StatusCode::OK, json!({ "num_pubkeys": num_pubkeys })),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+    }
+}
+
+fn json_response(status: StatusCode, body: Value) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+fn error_response(status: StatusCode, error: &str) -> Response<Body> {
+    json_response(status, json!({ "error": error }))
+}
@@ -1,15 +1,43 @@
+mod admin;
+mod metrics;
+mod readiness;
+
+use crate::admin::{serve_admin, AdminState};
+use crate::metrics::serve_metrics;
+use crate::readiness::Readiness;
 use clap::Parser;
+use futures::future::{join_all, select_all};
+use jito_auth::allowlist::Allowlist;
+use jito_auth::interceptor::AuthInterceptor;
 use jito_auth::server::AuthServiceImpl;
+use jito_auth::token::TokenStore;
+use jito_leader_schedule::tracker::LeaderScheduleTracker;
 use jito_protos::auth::auth_service_server::AuthServiceServer;
 use jito_protos::block_engine::block_engine_validator_server::BlockEngineValidatorServer;
 use jito_protos::searcher::searcher_service_server::SearcherServiceServer;
+use jito_protos::searcher_ext::searcher_ext_service_server::SearcherExtServiceServer;
+use jito_searcher::bundle_validator::BundleValidator;
+use jito_searcher::connected_searchers::ConnectedSearchers;
+use jito_searcher::rate_limiter::RateLimiter;
 use jito_searcher::server::SearcherServiceImpl;
+use jito_validator::backpressure::BackpressurePolicy;
+use jito_validator::routing::RoutingMode;
 use jito_validator::server::ValidatorServerImpl;
-use log::info;
+use log::{error, info};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use std::fmt::Display;
+use std::future::Future;
 use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::process::exit;
 use tokio::runtime::Builder;
+use tokio::signal::ctrl_c;
+use tokio::signal::unix::{signal, SignalKind};
 use tokio::sync::mpsc::channel;
-use tonic::transport::Server;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tonic::transport::{NamedService, Server};
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
@@ -22,9 +50,107 @@ struct Args {
     #[clap(long, env, default_value = "0.0.0.0:1003")]
     validator_addr: SocketAddr,
 
-    /// Bind address for validator service
+    /// Bind address for auth service
     #[clap(long, env, default_value = "0.0.0.0:1005")]
     auth_addr: SocketAddr,
+
+    /// Bind address for all services. When set, the searcher, validator and auth services are
+    /// served from this single address and the per-service addresses are ignored.
+    #[clap(long, env)]
+    bind_addr: Option<SocketAddr>,
+
+    /// Bind address for the prometheus metrics endpoint, served at /metrics
+    #[clap(long, env, default_value = "0.0.0.0:9090")]
+    metrics_addr: SocketAddr,
+
+    /// Bind address for the admin API
+    #[clap(long, env, default_value = "127.0.0.1:9091")]
+    admin_addr: SocketAddr,
+
+    /// Path to a file of base58 pubkeys allowed to authenticate, one per line.
+    /// Every pubkey is allowed if unset.
+    #[clap(long, env)]
+    auth_allowlist_path: Option<PathBuf>,
+
+    /// Reject searcher and validator requests that don't carry an access token
+    #[clap(long, env)]
+    require_auth: bool,
+
+    /// RPC url used to follow the current slot and leader schedule, and to resolve the address
+    /// lookup tables of v0 transactions in bundles. Required for leader-only routing.
+    #[clap(long, env)]
+    rpc_url: Option<String>,
+
+    /// Which validators receive bundles: broadcast or leader_only
+    #[clap(long, env, default_value = "broadcast")]
+    routing_mode: RoutingMode,
+
+    /// What to do when a validator subscription falls behind: drop_newest, drop_oldest,
+    /// disconnect:<max consecutive full> or block:<timeout ms>
+    #[clap(long, env, default_value = "drop_newest")]
+    backpressure_policy: BackpressurePolicy,
+
+    /// Comma-separated tip accounts returned to searchers by GetTipAccounts
+    #[clap(long, env, value_delimiter = ',')]
+    tip_accounts: Vec<Pubkey>,
+
+    /// Bundles per second each searcher may send, keyed by pubkey or by IP when unauthenticated.
+    /// Unlimited if unset.
+    #[clap(long, env)]
+    searcher_rate_limit: Option<f64>,
+
+    /// Bundles a searcher may send at once before being held to --searcher-rate-limit
+    #[clap(long, env, default_value = "10")]
+    searcher_burst: u32,
+
+    /// Resend bundles that didn't reach a validator if it resubscribes within the leader's slot
+    /// window
+    #[clap(long, env)]
+    retry_bundle_deliveries: bool,
+}
+
+/// Readiness dependencies for the current slot and the leader schedule, only registered when an
+/// RPC url is set.
+const SLOT_TRACKER: &str = "slot_tracker";
+const LEADER_SCHEDULE: &str = "leader_schedule";
+
+/// Resolves on SIGINT or SIGTERM.
+async fn wait_for_shutdown_signal() {
+    let mut sigterm = signal(SignalKind::terminate()).expect("installs SIGTERM handler");
+    tokio::select! {
+        _ = ctrl_c() => {}
+        _ = sigterm.recv() => {}
+    }
+}
+
+/// Resolves once shutdown has been requested through the watch channel.
+async fn shutdown_requested(mut shutdown_receiver: watch::Receiver<bool>) {
+    while !*shutdown_receiver.borrow() {
+        if shutdown_receiver.changed().await.is_err() {
+            break;
+        }
+    }
+}
+
+/// Runs a server on the current runtime. The handle resolves to true if the server stopped
+/// cleanly.
+fn spawn_server<F, E>(name: &'static str, server: F) -> JoinHandle<bool>
+where
+    F: Future<Output = Result<(), E>> + Send + 'static,
+    E: Display,
+{
+    tokio::spawn(async move {
+        match server.await {
+            Ok(()) => {
+                info!("{} server stopped", name);
+                true
+            }
+            Err(e) => {
+                error!("{} server error: {}", name, e);
+                false
+            }
+        }
+    })
 }
 
 fn main() {
@@ -34,41 +160,232 @@ fn main() {
 
     let (_packet_sender, packet_receiver) = channel(100);
     let (bundle_sender, bundle_receiver) = channel(100);
+    let (shutdown_sender, shutdown_receiver) = watch::channel(false);
+
+    let allowlist = match &args.auth_allowlist_path {
+        Some(path) => Allowlist::from_path(path.clone()),
+        None => Allowlist::allow_all(),
+    };
+    // a bad allowlist is a configuration error, so it fails startup rather than being retried
+    if let Err(e) = allowlist.reload() {
+        error!("error loading --auth-allowlist-path: {}", e);
+        exit(1);
+    }
+    let token_store = TokenStore::default();
+    let auth_interceptor =
+        AuthInterceptor::new(token_store.clone(), allowlist.clone(), args.require_auth);
+    let connected_searchers = ConnectedSearchers::default();
+    let rate_limiter = match args.searcher_rate_limit {
+        Some(rate) if rate <= 0.0 || args.searcher_burst == 0 => {
+            error!("--searcher-rate-limit and --searcher-burst must be positive");
+            exit(1);
+        }
+        Some(rate) => Some(RateLimiter::new(rate, args.searcher_burst)),
+        None => None,
+    };
+
+    let (leader_schedule, leader_schedule_updater) = match &args.rpc_url {
+        Some(rpc_url) => {
+            let (tracker, updater) = LeaderScheduleTracker::new(
+                RpcClient::new(rpc_url.clone()),
+                shutdown_receiver.clone(),
+            );
+            (Some(tracker), Some(updater))
+        }
+        None => (None, None),
+    };
+    if args.routing_mode == RoutingMode::LeaderOnly && leader_schedule.is_none() {
+        error!("--routing-mode leader_only requires --rpc-url");
+        exit(1);
+    }
 
     let runtime = Builder::new_multi_thread().enable_all().build().unwrap();
-    runtime.block_on(async move {
-        // start searcher server
-        tokio::spawn(async move {
-            let searcher_service_impl = SearcherServiceImpl::new(bundle_sender);
-            let searcher_svc = SearcherServiceServer::new(searcher_service_impl);
+    let success = runtime.block_on(async move {
+        let mut dependencies = Vec::new();
+        if leader_schedule.is_some() {
+            dependencies.extend([SLOT_TRACKER, LEADER_SCHEDULE]);
+        }
+        let (health_reporter, health_svc) = tonic_health::server::health_reporter();
+        let readiness = Readiness::new(
+            health_reporter,
+            vec![
+                SearcherServiceServer::<SearcherServiceImpl>::NAME,
+                SearcherExtServiceServer::<SearcherServiceImpl>::NAME,
+                BlockEngineValidatorServer::<ValidatorServerImpl>::NAME,
+                AuthServiceServer::<AuthServiceImpl>::NAME,
+            ],
+            &dependencies,
+        )
+        .await;
+
+        let validator_impl = ValidatorServerImpl::new(
+            bundle_receiver,
+            packet_receiver,
+            shutdown_receiver.clone(),
+            args.routing_mode,
+            leader_schedule.clone(),
+            args.backpressure_policy,
+            args.retry_bundle_deliveries,
+        );
+        let searcher_impl = SearcherServiceImpl::new(
+            bundle_sender,
+            shutdown_receiver.clone(),
+            connected_searchers.clone(),
+            rate_limiter.clone(),
+            validator_impl.forwarder().clone(),
+            leader_schedule.clone(),
+            args.tip_accounts,
+            BundleValidator::new(args.rpc_url.map(RpcClient::new)),
+        );
+        let searcher_ext_svc = SearcherExtServiceServer::with_interceptor(
+            searcher_impl.clone(),
+            auth_interceptor.clone(),
+        );
+        let searcher_svc =
+            SearcherServiceServer::with_interceptor(searcher_impl, auth_interceptor.clone());
+        let auth_svc = AuthServiceServer::new(AuthServiceImpl::new(token_store, allowlist.clone()));
+        let validator_svc =
+            BlockEngineValidatorServer::with_interceptor(validator_impl.clone(), auth_interceptor);
+
+        let leader_schedule_updater = leader_schedule_updater.map(tokio::spawn);
+        if let Some(leader_schedule) = leader_schedule {
+            let slot_tracker = leader_schedule.clone();
+            let readiness = readiness.clone();
+            tokio::spawn(async move {
+                slot_tracker.wait_until_slot_loaded().await;
+                readiness.dependency_ready(SLOT_TRACKER).await;
+            });
+            let readiness = readiness.clone();
+            tokio::spawn(async move {
+                leader_schedule.wait_until_schedule_loaded().await;
+                readiness.dependency_ready(LEADER_SCHEDULE).await;
+            });
+        }
+
+        let mut servers = Vec::new();
+
+        info!("starting admin server at {}", args.admin_addr);
+        servers.push(spawn_server(
+            "admin",
+            serve_admin(
+                args.admin_addr,
+                AdminState {
+                    forwarder: validator_impl.forwarder().clone(),
+                    connected_searchers,
+                    rate_limiter,
+                    allowlist,
+                },
+                shutdown_requested(shutdown_receiver.clone()),
+            ),
+        ));
+
+        info!("starting metrics server at {}", args.metrics_addr);
+        servers.push(spawn_server(
+            "metrics",
+            serve_metrics(
+                args.metrics_addr,
+                readiness.clone(),
+                shutdown_requested(shutdown_receiver.clone()),
+            ),
+        ));
+
+        if let Some(bind_addr) = args.bind_addr {
+            info!(
+                "starting searcher, validator and auth servers at {}",
+                bind_addr
+            );
+            servers.push(spawn_server(
+                "block engine",
+                Server::builder()
+                    .add_service(health_svc)
+                    .add_service(searcher_svc)
+                    .add_service(searcher_ext_svc)
+                    .add_service(validator_svc)
+                    .add_service(auth_svc)
+                    .serve_with_shutdown(bind_addr, shutdown_requested(shutdown_receiver)),
+            ));
+        } else {
             info!("starting searcher server at {}", args.searcher_addr);
-            Server::builder()
-                .add_service(searcher_svc)
-                .serve(args.searcher_addr)
-                .await
-                .expect("searcher server starts");
-        });
-
-        // start auth server
-        tokio::spawn(async move {
-            let auth_service_impl = AuthServiceImpl::new();
-            let auth_svc = AuthServiceServer::new(auth_service_impl);
+            servers.push(spawn_server(
+                "searcher",
+                Server::builder()
+                    .add_service(health_svc.clone())
+                    .add_service(searcher_svc)
+                    .add_service(searcher_ext_svc)
+                    .serve_with_shutdown(
+                        args.searcher_addr,
+                        shutdown_requested(shutdown_receiver.clone()),
+                    ),
+            ));
+
             info!("starting auth server at {}", args.auth_addr);
-            Server::builder()
-                .add_service(auth_svc)
-                .serve(args.auth_addr)
-                .await
-                .expect("auth server starts");
-        });
-
-        // start validator server and block
-        let validator_impl = ValidatorServerImpl::new(bundle_receiver, packet_receiver);
-        let validator_svc = BlockEngineValidatorServer::new(validator_impl);
-        info!("starting validator server at {}", args.validator_addr);
-        Server::builder()
-            .add_service(validator_svc)
-            .serve(args.validator_addr)
+            servers.push(spawn_server(
+                "auth",
+                Server::builder()
+                    .add_service(health_svc.clone())
+                    .add_service(auth_svc)
+                    .serve_with_shutdown(
+                        args.auth_addr,
+                        shutdown_requested(shutdown_receiver.clone()),
+                    ),
+            ));
+
+            info!("starting validator server at {}", args.validator_addr);
+            servers.push(spawn_server(
+                "validator",
+                Server::builder()
+                    .add_service(health_svc.clone())
+                    .add_service(validator_svc)
+                    .serve_with_shutdown(
+                        args.validator_addr,
+                        shutdown_requested(shutdown_receiver),
+                    ),
+            ));
+        }
+
+        let mut running_servers = select_all(servers);
+        let exited_server = tokio::select! {
+            _ = wait_for_shutdown_signal() => None,
+            (_, _, remaining_servers) = &mut running_servers => Some(remaining_servers),
+        };
+        let (success, remaining_servers) = match exited_server {
+            None => {
+                info!("received shutdown signal, shutting down");
+                (true, running_servers.into_inner())
+            }
+            Some(remaining_servers) => {
+                error!("server exited before shutdown was requested, shutting down");
+                (false, remaining_servers)
+            }
+        };
+
+        readiness.shutting_down().await;
+
+        // stops accepting bundles and subscriptions; the forwarder drains its channels and
+        // closes the validator streams, which lets the servers finish their connections.
+        let _ = shutdown_sender.send(true);
+
+        let success = join_all(remaining_servers)
             .await
-            .expect("validator server starts");
+            .into_iter()
+            .fold(success, |success, result| {
+                success && matches!(result, Ok(true))
+            });
+        let success = match validator_impl.join().await {
+            Ok(()) => success,
+            Err(e) => {
+                error!("forwarder panicked: {:?}", e);
+                false
+            }
+        };
+        if let Some(leader_schedule_updater) = leader_schedule_updater {
+            let _ = leader_schedule_updater.await;
+        }
+        success
     });
+
+    if !success {
+        exit(1);
+    }
+    info!("block engine exited cleanly");
 }
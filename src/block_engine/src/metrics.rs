@@ -0,0 +1,71 @@
+use crate::readiness::Readiness;
+use hyper::header::CONTENT_TYPE;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use log::error;
+use prometheus::{Encoder, TextEncoder};
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::SocketAddr;
+
+/// Serves the default prometheus registry at `/metrics` and the readiness probe at `/ready` until
+/// `shutdown` resolves.
+pub async fn serve_metrics(
+    addr: SocketAddr,
+    readiness: Readiness,
+    shutdown: impl Future<Output = ()>,
+) -> Result<(), hyper::Error> {
+    let make_service = make_service_fn(move |_| {
+        let readiness = readiness.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                handle_request(request, readiness.clone())
+            }))
+        }
+    });
+    Server::try_bind(&addr)?
+        .serve(make_service)
+        .with_graceful_shutdown(shutdown)
+        .await
+}
+
+async fn handle_request(
+    request: Request<Body>,
+    readiness: Readiness,
+) -> Result<Response<Body>, Infallible> {
+    let response = match (request.method(), request.uri().path()) {
+        (&Method::GET, "/metrics") => {
+            let encoder = TextEncoder::new();
+            let mut buffer = Vec::new();
+            match encoder.encode(&prometheus::gather(), &mut buffer) {
+                Ok(()) => Response::builder()
+                    .header(CONTENT_TYPE, encoder.format_type())
+                    .body(Body::from(buffer))
+                    .unwrap(),
+                Err(e) => {
+                    error!("error encoding metrics: {}", e);
+                    Response::builder()
+                        .status(StatusCode::INTERNAL_SERVER_ERROR)
+                        .body(Body::empty())
+                        .unwrap()
+                }
+            }
+        }
+        (&Method::GET, "/ready") => {
+            let status = if readiness.is_ready() {
+                StatusCode::OK
+            } else {
+                StatusCode::SERVICE_UNAVAILABLE
+            };
+            Response::builder()
+                .status(status)
+                .body(Body::empty())
+                .unwrap()
+        }
+        _ => Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::empty())
+            .unwrap(),
+    };
+    Ok(response)
+}
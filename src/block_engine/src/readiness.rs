@@ -0,0 +1,82 @@
+use std::collections::HashSet;
+use std::iter::once;
+use std::sync::{Arc, Mutex};
+use tonic_health::server::HealthReporter;
+use tonic_health::ServingStatus;
+
+struct ReadinessState {
+    pending_dependencies: HashSet<&'static str>,
+    shutting_down: bool,
+}
+
+impl ReadinessState {
+    fn is_ready(&self) -> bool {
+        self.pending_dependencies.is_empty() && !self.shutting_down
+    }
+}
+
+/// Tracks the dependencies the block engine needs before it can serve traffic and publishes the
+/// result through the gRPC health service. Every service reports NOT_SERVING until all
+/// dependencies are loaded and again once shutdown starts.
+#[derive(Clone)]
+pub struct Readiness {
+    health_reporter: Arc<tokio::sync::Mutex<HealthReporter>>,
+    service_names: Arc<Vec<&'static str>>,
+    state: Arc<Mutex<ReadinessState>>,
+}
+
+impl Readiness {
+    pub async fn new(
+        health_reporter: HealthReporter,
+        service_names: Vec<&'static str>,
+        dependencies: &[&'static str],
+    ) -> Self {
+        let readiness = Readiness {
+            health_reporter: Arc::new(tokio::sync::Mutex::new(health_reporter)),
+            service_names: Arc::new(service_names),
+            state: Arc::new(Mutex::new(ReadinessState {
+                pending_dependencies: dependencies.iter().copied().collect(),
+                shutting_down: false,
+            })),
+        };
+        readiness.update(|_| {}).await;
+        readiness
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.state.lock().unwrap().is_ready()
+    }
+
+    /// Marks a dependency as loaded.
+    pub async fn dependency_ready(&self, dependency: &'static str) {
+        self.update(|state| {
+            state.pending_dependencies.remove(dependency);
+        })
+        .await;
+    }
+
+    pub async fn shutting_down(&self) {
+        self.update(|state| state.shutting_down = true).await;
+    }
+
+    async fn update(&self, f: impl FnOnce(&mut ReadinessState)) {
+        // held across the update so concurrent callers publish in the order they changed state
+        let mut health_reporter = self.health_reporter.lock().await;
+        let status = {
+            let mut state = self.state.lock().unwrap();
+            f(&mut state);
+            if state.is_ready() {
+                ServingStatus::Serving
+            } else {
+                ServingStatus::NotServing
+            }
+        };
+
+        // the empty service name is the server's overall status
+        for service_name in self.service_names.iter().chain(once(&"")) {
+            health_reporter
+                .set_service_status(service_name, status)
+                .await;
+        }
+    }
+}
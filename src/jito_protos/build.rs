@@ -12,8 +12,10 @@ fn main() {
                 "protos/relayer.proto",
                 "protos/searcher.proto",
                 "protos/shared.proto",
+                // protos that aren't part of the mev-protos submodule
+                "protos_ext/searcher_ext.proto",
             ],
-            &["protos"],
+            &["protos", "protos_ext"],
         )
         .unwrap();
 }
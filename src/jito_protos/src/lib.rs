@@ -1,5 +1,8 @@
-use bincode::serialize;
+use bincode::{serialize, Options};
+use solana_sdk::packet::PACKET_DATA_SIZE;
 use solana_sdk::transaction::VersionedTransaction;
+use std::fmt;
+use std::time::SystemTime;
 
 pub mod auth {
     tonic::include_proto!("auth");
@@ -29,22 +32,264 @@ pub mod searcher {
     tonic::include_proto!("searcher");
 }
 
+pub mod searcher_ext {
+    tonic::include_proto!("searcher_ext");
+}
+
 pub mod shared {
     tonic::include_proto!("shared");
 }
 
-/// Converts a VersionedTransaction to a protobuf packet
-pub fn proto_packet_from_versioned_tx(tx: &VersionedTransaction) -> packet::Packet {
-    let data = serialize(tx).expect("serializes");
+#[derive(Debug)]
+pub enum ConversionError {
+    Serialize(bincode::Error),
+    /// The packet doesn't hold a transaction of at most [PACKET_DATA_SIZE] bytes.
+    Deserialize(bincode::Error),
+    /// Converting the bundle's packet or transaction at the index failed.
+    InBundle(usize, Box<ConversionError>),
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConversionError::Serialize(e) => write!(f, "error serializing transaction: {}", e),
+            ConversionError::Deserialize(e) => write!(f, "error deserializing transaction: {}", e),
+            ConversionError::InBundle(index, e) => write!(f, "transaction {}: {}", index, e),
+        }
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+/// A header stamped with the current time.
+pub fn header_now() -> shared::Header {
+    shared::Header {
+        ts: Some(prost_types::Timestamp::from(SystemTime::now())),
+    }
+}
+
+impl packet::Packet {
+    /// The packet's flags, if its meta has any.
+    pub fn flags(&self) -> Option<&packet::PacketFlags> {
+        self.meta.as_ref().and_then(|meta| meta.flags.as_ref())
+    }
+
+    /// The packet's flags, adding empty meta and flags if they're missing.
+    pub fn flags_mut(&mut self) -> &mut packet::PacketFlags {
+        self.meta
+            .get_or_insert_with(packet::Meta::default)
+            .flags
+            .get_or_insert_with(packet::PacketFlags::default)
+    }
+}
+
+/// Converts a VersionedTransaction to a protobuf packet, with its size and empty flags in the
+/// meta.
+pub fn proto_packet_from_versioned_tx(
+    tx: &VersionedTransaction,
+) -> Result<packet::Packet, ConversionError> {
+    let data = serialize(tx).map_err(ConversionError::Serialize)?;
     let size = data.len() as u64;
-    packet::Packet {
+    Ok(packet::Packet {
         data,
         meta: Some(packet::Meta {
             size,
             addr: "".to_string(),
             port: 0,
-            flags: None,
+            flags: Some(packet::PacketFlags::default()),
             sender_stake: 0,
         }),
+    })
+}
+
+/// Deserializes the transaction in a protobuf packet the way validators do: at most
+/// [PACKET_DATA_SIZE] bytes, ignoring bytes past the meta's size if it has one.
+pub fn versioned_tx_from_packet(
+    packet: &packet::Packet,
+) -> Result<VersionedTransaction, ConversionError> {
+    let size = match &packet.meta {
+        Some(meta) if meta.size > 0 => (meta.size as usize).min(packet.data.len()),
+        _ => packet.data.len(),
+    };
+    bincode::options()
+        .with_limit(PACKET_DATA_SIZE as u64)
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .deserialize(&packet.data[..size])
+        .map_err(ConversionError::Deserialize)
+}
+
+/// Converts transactions to a bundle stamped with the current time.
+pub fn bundle_from_versioned_txs(
+    txs: &[VersionedTransaction],
+) -> Result<bundle::Bundle, ConversionError> {
+    let packets = txs
+        .iter()
+        .enumerate()
+        .map(|(index, tx)| {
+            proto_packet_from_versioned_tx(tx)
+                .map_err(|e| ConversionError::InBundle(index, Box::new(e)))
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(bundle::Bundle {
+        header: Some(header_now()),
+        packets,
+    })
+}
+
+/// Deserializes every transaction of a bundle.
+pub fn versioned_txs_from_bundle(
+    bundle: &bundle::Bundle,
+) -> Result<Vec<VersionedTransaction>, ConversionError> {
+    bundle
+        .packets
+        .iter()
+        .enumerate()
+        .map(|(index, packet)| {
+            versioned_tx_from_packet(packet)
+                .map_err(|e| ConversionError::InBundle(index, Box::new(e)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::hash::Hash;
+    use solana_sdk::instruction::{CompiledInstruction, Instruction};
+    use solana_sdk::message::v0::{self, MessageAddressTableLookup};
+    use solana_sdk::message::{MessageHeader, VersionedMessage};
+    use solana_sdk::pubkey::Pubkey;
+    use solana_sdk::signature::{Keypair, Signer};
+    use solana_sdk::system_program;
+    use solana_sdk::system_transaction;
+    use solana_sdk::transaction::Transaction;
+
+    fn legacy_tx() -> VersionedTransaction {
+        VersionedTransaction::from(system_transaction::transfer(
+            &Keypair::new(),
+            &Pubkey::new_unique(),
+            1,
+            Hash::new_unique(),
+        ))
+    }
+
+    fn v0_tx() -> VersionedTransaction {
+        let payer = Keypair::new();
+        let message = VersionedMessage::V0(v0::Message {
+            header: MessageHeader {
+                num_required_signatures: 1,
+                num_readonly_signed_accounts: 0,
+                num_readonly_unsigned_accounts: 1,
+            },
+            account_keys: vec![payer.pubkey(), system_program::id()],
+            recent_blockhash: Hash::new_unique(),
+            // the second account comes from the lookup table
+            instructions: vec![CompiledInstruction {
+                program_id_index: 1,
+                accounts: vec![0, 2],
+                data: vec![],
+            }],
+            address_table_lookups: vec![MessageAddressTableLookup {
+                account_key: Pubkey::new_unique(),
+                writable_indexes: vec![3],
+                readonly_indexes: vec![],
+            }],
+        });
+        VersionedTransaction::try_new(message, &[&payer]).unwrap()
+    }
+
+    #[test]
+    fn test_packet_round_trip() {
+        for tx in [legacy_tx(), v0_tx()] {
+            let packet = proto_packet_from_versioned_tx(&tx).unwrap();
+            assert_eq!(packet.meta.as_ref().unwrap().size, packet.data.len() as u64);
+            assert_eq!(versioned_tx_from_packet(&packet).unwrap(), tx);
+        }
+    }
+
+    #[test]
+    fn test_bundle_round_trip() {
+        let txs = vec![legacy_tx(), v0_tx(), legacy_tx()];
+        let bundle = bundle_from_versioned_txs(&txs).unwrap();
+        assert!(bundle.header.is_some());
+        assert_eq!(bundle.packets.len(), 3);
+        assert_eq!(versioned_txs_from_bundle(&bundle).unwrap(), txs);
+    }
+
+    #[test]
+    fn test_garbage_fails_to_deserialize() {
+        let packet = packet::Packet {
+            data: vec![0xff; 64],
+            meta: None,
+        };
+        assert!(matches!(
+            versioned_tx_from_packet(&packet),
+            Err(ConversionError::Deserialize(_))
+        ));
+    }
+
+    #[test]
+    fn test_oversized_fails_to_deserialize() {
+        let payer = Keypair::new();
+        let instruction =
+            Instruction::new_with_bytes(Pubkey::new_unique(), &[0; PACKET_DATA_SIZE], vec![]);
+        let tx = Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&payer.pubkey()),
+            &[&payer],
+            Hash::new_unique(),
+        );
+        let packet = proto_packet_from_versioned_tx(&VersionedTransaction::from(tx)).unwrap();
+        assert!(packet.data.len() > PACKET_DATA_SIZE);
+        assert!(matches!(
+            versioned_tx_from_packet(&packet),
+            Err(ConversionError::Deserialize(_))
+        ));
+    }
+
+    #[test]
+    fn test_meta_size_truncates_data() {
+        let tx = legacy_tx();
+        let mut packet = proto_packet_from_versioned_tx(&tx).unwrap();
+        let size = packet.data.len() as u64;
+
+        // bytes past the size are ignored
+        packet.data.extend_from_slice(&[0xff; 16]);
+        assert_eq!(versioned_tx_from_packet(&packet).unwrap(), tx);
+
+        // a size shorter than the transaction cuts it off
+        packet.meta.as_mut().unwrap().size = size - 1;
+        assert!(matches!(
+            versioned_tx_from_packet(&packet),
+            Err(ConversionError::Deserialize(_))
+        ));
+
+        // a size past the data is capped at the data's length
+        packet.meta.as_mut().unwrap().size = size + 1_000;
+        assert_eq!(versioned_tx_from_packet(&packet).unwrap(), tx);
+    }
+
+    #[test]
+    fn test_bundle_error_carries_index() {
+        let mut bundle = bundle_from_versioned_txs(&[legacy_tx(), v0_tx(), legacy_tx()]).unwrap();
+        bundle.packets[1].data.truncate(10);
+        bundle.packets[1].meta = None;
+        match versioned_txs_from_bundle(&bundle) {
+            Err(ConversionError::InBundle(index, e)) => {
+                assert_eq!(index, 1);
+                assert!(matches!(*e, ConversionError::Deserialize(_)));
+            }
+            result => panic!("expected an error for packet 1, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_flags_mut_adds_meta() {
+        let mut packet = packet::Packet::default();
+        assert!(packet.flags().is_none());
+        packet.flags_mut().simple_vote_tx = true;
+        assert!(packet.meta.is_some());
+        assert!(packet.flags().unwrap().simple_vote_tx);
     }
 }
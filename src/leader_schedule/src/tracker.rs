@@ -0,0 +1,383 @@
+use log::{info, warn};
+use solana_client::client_error::ClientError;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::clock::{Epoch, Slot};
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::interval;
+
+#[derive(Default)]
+struct LeaderScheduleState {
+    current_slot: Option<Slot>,
+    /// First slot of each epoch whose schedule is loaded: the current epoch and, once it's been
+    /// fetched, the next one.
+    epoch_start_slots: BTreeMap<Epoch, Slot>,
+    slot_leaders: BTreeMap<Slot, Pubkey>,
+    /// The same schedule by leader, for looking up the slots of a few identities.
+    leader_slots: HashMap<Pubkey, BTreeSet<Slot>>,
+}
+
+impl LeaderScheduleState {
+    fn add_epoch(&mut self, epoch: Epoch, first_slot: Slot, slot_leaders: Vec<(Slot, Pubkey)>) {
+        for (slot, leader) in slot_leaders {
+            self.slot_leaders.insert(slot, leader);
+            self.leader_slots.entry(leader).or_default().insert(slot);
+        }
+        self.epoch_start_slots.insert(epoch, first_slot);
+    }
+
+    /// Drops the schedules of epochs before `epoch`, which starts at `first_slot`.
+    fn drop_epochs_before(&mut self, epoch: Epoch, first_slot: Slot) {
+        if self.epoch_start_slots.range(..epoch).next().is_none() {
+            return;
+        }
+        self.epoch_start_slots = self.epoch_start_slots.split_off(&epoch);
+        self.slot_leaders = self.slot_leaders.split_off(&first_slot);
+        self.leader_slots.retain(|_, slots| {
+            *slots = slots.split_off(&first_slot);
+            !slots.is_empty()
+        });
+    }
+
+    /// The first slot after the current slot's epoch, if the schedule after it is loaded.
+    fn current_epoch_end(&self, current_slot: Slot) -> Option<Slot> {
+        self.epoch_start_slots
+            .values()
+            .copied()
+            .find(|first_slot| *first_slot > current_slot)
+    }
+
+    fn upcoming_leaders(&self, lookahead_slots: u64) -> Option<HashSet<Pubkey>> {
+        let current_slot = self.current_slot?;
+        let slots = current_slot..=current_slot + lookahead_slots;
+        let num_slots = slots.clone().count();
+        let leaders: Vec<Pubkey> = self
+            .slot_leaders
+            .range(slots)
+            .map(|(_, leader)| *leader)
+            .collect();
+        (leaders.len() == num_slots).then(|| leaders.into_iter().collect())
+    }
+
+    fn upcoming_leader_slots(&self, identities: &HashSet<Pubkey>) -> HashMap<Pubkey, Vec<Slot>> {
+        let current_slot = match self.current_slot {
+            Some(current_slot) => current_slot,
+            None => return HashMap::default(),
+        };
+        let epoch_end = self.current_epoch_end(current_slot);
+        identities
+            .iter()
+            .filter_map(|identity| {
+                let slots = self.leader_slots.get(identity)?;
+                let slots: Vec<Slot> = match epoch_end {
+                    Some(epoch_end) => slots.range(current_slot..epoch_end).copied().collect(),
+                    None => slots.range(current_slot..).copied().collect(),
+                };
+                (!slots.is_empty()).then(|| (*identity, slots))
+            })
+            .collect()
+    }
+
+    fn next_leader_slot(&self, identities: &HashSet<Pubkey>) -> Option<(Slot, Pubkey)> {
+        let current_slot = self.current_slot?;
+        identities
+            .iter()
+            .filter_map(|identity| {
+                let slot = self
+                    .leader_slots
+                    .get(identity)?
+                    .range(current_slot..)
+                    .next()?;
+                Some((*slot, *identity))
+            })
+            .min_by_key(|(slot, _)| *slot)
+    }
+}
+
+/// Follows the cluster's current slot and the leader schedules for the current and next epochs by
+/// polling an RPC node.
+#[derive(Clone)]
+pub struct LeaderScheduleTracker {
+    state: Arc<RwLock<LeaderScheduleState>>,
+    slot_loaded_receiver: watch::Receiver<bool>,
+    schedule_loaded_receiver: watch::Receiver<bool>,
+}
+
+impl LeaderScheduleTracker {
+    const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+    /// Returns the tracker and the future that keeps it updated until shutdown is requested.
+    pub fn new(
+        rpc_client: RpcClient,
+        shutdown_receiver: watch::Receiver<bool>,
+    ) -> (Self, impl std::future::Future<Output = ()>) {
+        let (slot_loaded_sender, slot_loaded_receiver) = watch::channel(false);
+        let (schedule_loaded_sender, schedule_loaded_receiver) = watch::channel(false);
+        let tracker = LeaderScheduleTracker {
+            state: Arc::new(RwLock::new(LeaderScheduleState::default())),
+            slot_loaded_receiver,
+            schedule_loaded_receiver,
+        };
+        let updater = Self::run(
+            tracker.state.clone(),
+            rpc_client,
+            slot_loaded_sender,
+            schedule_loaded_sender,
+            shutdown_receiver,
+        );
+        (tracker, updater)
+    }
+
+    /// Resolves once the current slot has been fetched.
+    pub async fn wait_until_slot_loaded(&self) {
+        Self::wait_until(self.slot_loaded_receiver.clone()).await
+    }
+
+    /// Resolves once a leader schedule has been fetched.
+    pub async fn wait_until_schedule_loaded(&self) {
+        Self::wait_until(self.schedule_loaded_receiver.clone()).await
+    }
+
+    async fn wait_until(mut loaded_receiver: watch::Receiver<bool>) {
+        while !*loaded_receiver.borrow() {
+            if loaded_receiver.changed().await.is_err() {
+                break;
+            }
+        }
+    }
+
+    pub fn current_slot(&self) -> Option<Slot> {
+        self.state.read().unwrap().current_slot
+    }
+
+    pub fn leader_for_slot(&self, slot: Slot) -> Option<Pubkey> {
+        self.state.read().unwrap().slot_leaders.get(&slot).copied()
+    }
+
+    /// Leaders of the current slot and the `lookahead_slots` slots after it, or None if the
+    /// loaded schedule doesn't cover all of them, e.g. past an epoch boundary before the next
+    /// epoch's schedule has been fetched.
+    pub fn upcoming_leaders(&self, lookahead_slots: u64) -> Option<HashSet<Pubkey>> {
+        self.state.read().unwrap().upcoming_leaders(lookahead_slots)
+    }
+
+    /// Each identity's leader slots from the current slot to the end of the epoch, in order.
+    pub fn upcoming_leader_slots(
+        &self,
+        identities: &HashSet<Pubkey>,
+    ) -> HashMap<Pubkey, Vec<Slot>> {
+        self.state.read().unwrap().upcoming_leader_slots(identities)
+    }
+
+    /// The first slot from the current one to the end of the loaded schedule led by one of the
+    /// identities, and its leader. The schedule includes the next epoch once it's been fetched.
+    pub fn next_leader_slot(&self, identities: &HashSet<Pubkey>) -> Option<(Slot, Pubkey)> {
+        self.state.read().unwrap().next_leader_slot(identities)
+    }
+
+    async fn run(
+        state: Arc<RwLock<LeaderScheduleState>>,
+        rpc_client: RpcClient,
+        slot_loaded_sender: watch::Sender<bool>,
+        schedule_loaded_sender: watch::Sender<bool>,
+        mut shutdown_receiver: watch::Receiver<bool>,
+    ) {
+        let mut poll_interval = interval(Self::POLL_INTERVAL);
+        while !*shutdown_receiver.borrow() {
+            tokio::select! {
+                result = shutdown_receiver.changed() => {
+                    if result.is_err() {
+                        break;
+                    }
+                }
+                _ = poll_interval.tick() => {
+                    match Self::update(&state, &rpc_client).await {
+                        Ok(()) => {
+                            let (slot_loaded, schedule_loaded) = {
+                                let state = state.read().unwrap();
+                                (state.current_slot.is_some(), !state.epoch_start_slots.is_empty())
+                            };
+                            if slot_loaded && !*slot_loaded_sender.borrow() {
+                                info!("current slot loaded");
+                                let _ = slot_loaded_sender.send(true);
+                            }
+                            if schedule_loaded && !*schedule_loaded_sender.borrow() {
+                                info!("leader schedule loaded");
+                                let _ = schedule_loaded_sender.send(true);
+                            }
+                        }
+                        Err(e) => {
+                            warn!("error updating leader schedule: {}", e);
+                        }
+                    }
+                }
+            }
+        }
+        info!("leader schedule tracker exiting");
+    }
+
+    /// Updates the current slot, and fetches the schedules of the current and next epochs if
+    /// they aren't loaded. The next epoch's schedule is fetched ahead of time so lookups don't
+    /// stop at the epoch boundary. A schedule the RPC node doesn't have is retried on the next
+    /// poll.
+    async fn update(
+        state: &Arc<RwLock<LeaderScheduleState>>,
+        rpc_client: &RpcClient,
+    ) -> Result<(), ClientError> {
+        let epoch_info = rpc_client
+            .get_epoch_info_with_commitment(CommitmentConfig::processed())
+            .await?;
+        let first_slot_in_epoch = epoch_info.absolute_slot - epoch_info.slot_index;
+        {
+            let mut state = state.write().unwrap();
+            state.current_slot = Some(epoch_info.absolute_slot);
+            state.drop_epochs_before(epoch_info.epoch, first_slot_in_epoch);
+        }
+
+        let epochs = [
+            (epoch_info.epoch, first_slot_in_epoch),
+            (
+                epoch_info.epoch + 1,
+                first_slot_in_epoch + epoch_info.slots_in_epoch,
+            ),
+        ];
+        for (epoch, first_slot) in epochs {
+            if state.read().unwrap().epoch_start_slots.contains_key(&epoch) {
+                continue;
+            }
+            let slot_leaders = match Self::fetch_schedule(rpc_client, first_slot).await? {
+                Some(slot_leaders) => slot_leaders,
+                None => {
+                    warn!("no leader schedule for epoch {}", epoch);
+                    return Ok(());
+                }
+            };
+            info!(
+                "loaded leader schedule epoch: {} slots: {}",
+                epoch,
+                slot_leaders.len()
+            );
+            state
+                .write()
+                .unwrap()
+                .add_epoch(epoch, first_slot, slot_leaders);
+        }
+        Ok(())
+    }
+
+    /// Fetches the leader of each slot in the epoch starting at `first_slot`.
+    async fn fetch_schedule(
+        rpc_client: &RpcClient,
+        first_slot: Slot,
+    ) -> Result<Option<Vec<(Slot, Pubkey)>>, ClientError> {
+        let schedule = match rpc_client
+            .get_leader_schedule_with_commitment(Some(first_slot), CommitmentConfig::processed())
+            .await?
+        {
+            Some(schedule) => schedule,
+            None => return Ok(None),
+        };
+
+        // the schedule is keyed by identity with slots relative to the start of the epoch
+        let mut slot_leaders = Vec::new();
+        for (identity, slot_indices) in schedule {
+            let identity = match Pubkey::from_str(&identity) {
+                Ok(identity) => identity,
+                Err(_) => {
+                    warn!("invalid leader identity in schedule: {}", identity);
+                    continue;
+                }
+            };
+            slot_leaders.extend(
+                slot_indices
+                    .into_iter()
+                    .map(|slot_index| (first_slot + slot_index as Slot, identity)),
+            );
+        }
+        Ok(Some(slot_leaders))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SLOTS_PER_EPOCH: Slot = 8;
+
+    /// Epochs 0 and 1, each led by one identity for the first half and another for the second.
+    fn two_epochs(leaders: &[Pubkey; 4]) -> LeaderScheduleState {
+        let mut state = LeaderScheduleState::default();
+        for epoch in 0..2 {
+            let first_slot = epoch * SLOTS_PER_EPOCH;
+            let slot_leaders = (first_slot..first_slot + SLOTS_PER_EPOCH)
+                .map(|slot| (slot, leaders[(slot / 4) as usize]))
+                .collect();
+            state.add_epoch(epoch, first_slot, slot_leaders);
+        }
+        state
+    }
+
+    #[test]
+    fn test_upcoming_leaders_across_epoch_boundary() {
+        let leaders = [(); 4].map(|_| Pubkey::new_unique());
+        let mut state = two_epochs(&leaders);
+        state.current_slot = Some(6);
+        assert_eq!(
+            state.upcoming_leaders(4),
+            Some(HashSet::from([leaders[1], leaders[2]]))
+        );
+        assert_eq!(
+            state.next_leader_slot(&HashSet::from([leaders[2]])),
+            Some((8, leaders[2]))
+        );
+
+        // other identities' slots stop at the end of the current epoch
+        let identities = HashSet::from([leaders[0], leaders[1], leaders[2]]);
+        assert_eq!(
+            state.upcoming_leader_slots(&identities),
+            HashMap::from([(leaders[1], vec![6, 7])])
+        );
+    }
+
+    #[test]
+    fn test_upcoming_leaders_unknown_past_loaded_schedule() {
+        let leaders = [(); 4].map(|_| Pubkey::new_unique());
+        let mut state = two_epochs(&leaders);
+        state.current_slot = Some(14);
+        assert_eq!(state.upcoming_leaders(1), Some(HashSet::from([leaders[3]])));
+        // epoch 2 isn't loaded, so the leaders of slots 16 and 17 are unknown
+        assert_eq!(state.upcoming_leaders(3), None);
+
+        state.current_slot = None;
+        assert_eq!(state.upcoming_leaders(0), None);
+    }
+
+    #[test]
+    fn test_drop_epochs_before() {
+        let leaders = [(); 4].map(|_| Pubkey::new_unique());
+        let mut state = two_epochs(&leaders);
+        state.drop_epochs_before(1, SLOTS_PER_EPOCH);
+        assert_eq!(
+            state.epoch_start_slots,
+            BTreeMap::from([(1, SLOTS_PER_EPOCH)])
+        );
+        assert_eq!(state.slot_leaders.keys().next(), Some(&SLOTS_PER_EPOCH));
+        assert!(!state.leader_slots.contains_key(&leaders[0]));
+        assert!(!state.leader_slots.contains_key(&leaders[1]));
+
+        state.current_slot = Some(9);
+        let identities = HashSet::from([leaders[2], leaders[3]]);
+        assert_eq!(
+            state.upcoming_leader_slots(&identities),
+            HashMap::from([
+                (leaders[2], vec![9, 10, 11]),
+                (leaders[3], vec![12, 13, 14, 15])
+            ])
+        );
+    }
+}
@@ -0,0 +1,146 @@
+use jito_protos::searcher_ext::{BundleState, BundleStatus};
+use jito_validator::forwarder::{BundleEventKind, ForwardedBundle, Forwarder};
+use std::collections::BTreeSet;
+
+/// The bundle's status as recorded by the forwarder. Delivered means a validator's gRPC stream took
+/// the bundle to write to its connection, not that the validator acknowledged it.
+pub fn bundle_status(forwarder: &Forwarder, uuid: &str) -> BundleStatus {
+    match forwarder.bundle(uuid) {
+        Some(bundle) => forwarded_status(bundle),
+        None => BundleStatus {
+            uuid: uuid.to_string(),
+            state: BundleState::Unknown as i32,
+            ..BundleStatus::default()
+        },
+    }
+}
+
+fn forwarded_status(bundle: ForwardedBundle) -> BundleStatus {
+    let mut delivered_to = BTreeSet::new();
+    let mut undelivered = 0;
+    for event in &bundle.events {
+        match event.kind {
+            BundleEventKind::Delivered => {
+                if let Some(identity) = event.identity {
+                    delivered_to.insert(identity.to_string());
+                }
+            }
+            BundleEventKind::Dropped {
+                reason: "undelivered",
+            } => undelivered += 1,
+            _ => {}
+        }
+    }
+    // sent bundles end up either delivered or undelivered; other drops happen before sending
+    let in_flight = bundle
+        .validators_sent
+        .saturating_sub(bundle.validators_delivered + undelivered);
+    let state = if bundle.validators_delivered > 0 {
+        BundleState::Delivered
+    } else if in_flight == 0 && bundle.validators_dropped > 0 {
+        BundleState::Dropped
+    } else {
+        BundleState::Forwarded
+    };
+
+    BundleStatus {
+        uuid: bundle.uuid,
+        state: state as i32,
+        validators_sent: bundle.validators_sent as u32,
+        validators_delivered: bundle.validators_delivered as u32,
+        validators_dropped: bundle.validators_dropped as u32,
+        delivered_to: delivered_to.into_iter().collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jito_validator::forwarder::BundleEvent;
+    use solana_sdk::pubkey::Pubkey;
+    use std::time::SystemTime;
+    use uuid::Uuid;
+
+    fn event(identity: Pubkey, kind: BundleEventKind) -> BundleEvent {
+        BundleEvent {
+            at: SystemTime::now(),
+            subscription: Uuid::new_v4(),
+            identity: Some(identity),
+            kind,
+        }
+    }
+
+    fn bundle(sent: usize, events: Vec<BundleEvent>) -> ForwardedBundle {
+        let count = |f: fn(&BundleEventKind) -> bool| events.iter().filter(|e| f(&e.kind)).count();
+        ForwardedBundle {
+            uuid: "uuid".to_string(),
+            forwarded_at: SystemTime::now(),
+            num_packets: 1,
+            validators_sent: sent,
+            validators_delivered: count(|kind| matches!(kind, BundleEventKind::Delivered)),
+            validators_dropped: count(|kind| matches!(kind, BundleEventKind::Dropped { .. })),
+            events,
+        }
+    }
+
+    fn state(bundle: ForwardedBundle) -> BundleState {
+        BundleState::from_i32(forwarded_status(bundle).state).unwrap()
+    }
+
+    #[test]
+    fn test_forwarded_while_in_flight() {
+        assert_eq!(state(bundle(0, vec![])), BundleState::Forwarded);
+        assert_eq!(state(bundle(2, vec![])), BundleState::Forwarded);
+
+        // one validator didn't get it, the other may still
+        let identity = Pubkey::new_unique();
+        let dropped = BundleEventKind::Dropped {
+            reason: "undelivered",
+        };
+        assert_eq!(
+            state(bundle(2, vec![event(identity, dropped)])),
+            BundleState::Forwarded
+        );
+    }
+
+    #[test]
+    fn test_delivered_to_any_validator() {
+        let delivered = Pubkey::new_unique();
+        let dropped = Pubkey::new_unique();
+        let status = forwarded_status(bundle(
+            2,
+            vec![
+                event(
+                    dropped,
+                    BundleEventKind::Dropped {
+                        reason: "undelivered",
+                    },
+                ),
+                event(delivered, BundleEventKind::Delivered),
+            ],
+        ));
+        assert_eq!(status.state, BundleState::Delivered as i32);
+        assert_eq!(status.delivered_to, vec![delivered.to_string()]);
+    }
+
+    #[test]
+    fn test_dropped_everywhere() {
+        let identity = Pubkey::new_unique();
+        // dropped before it was sent, e.g. on a full queue
+        let queue_full = BundleEventKind::Dropped {
+            reason: "queue_full",
+        };
+        assert_eq!(
+            state(bundle(0, vec![event(identity, queue_full)])),
+            BundleState::Dropped
+        );
+
+        let undelivered = BundleEventKind::Dropped {
+            reason: "undelivered",
+        };
+        assert_eq!(
+            state(bundle(1, vec![event(identity, undelivered)])),
+            BundleState::Dropped
+        );
+    }
+}
@@ -0,0 +1,502 @@
+use jito_protos::bundle::Bundle;
+use jito_protos::packet::Packet;
+use jito_protos::versioned_tx_from_packet;
+use log::debug;
+use solana_address_lookup_table_program::state::AddressLookupTable;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::account::Account;
+use solana_sdk::message::v0::MessageAddressTableLookup;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::transaction::SanitizedVersionedTransaction;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::spawn_blocking;
+use tokio::time::timeout;
+
+/// How long a lookup table's length is trusted before it's fetched again. Tables only grow while
+/// active, so a cached length is only stale for indexes past it, which trigger a refetch anyway;
+/// the TTL bounds how long a closed table keeps resolving.
+const LOOKUP_TABLE_TTL: Duration = Duration::from_secs(60);
+
+/// How long an account is remembered as not being a lookup table. Shorter than
+/// [LOOKUP_TABLE_TTL] so a table created right after a bundle referenced it soon resolves.
+const NOT_A_TABLE_TTL: Duration = Duration::from_secs(10);
+
+/// Longest a `getMultipleAccounts` request may take before the bundle is rejected.
+const LOOKUP_RPC_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Most accounts `getMultipleAccounts` returns per request.
+const MAX_ACCOUNTS_PER_REQUEST: usize = 100;
+
+#[derive(Debug)]
+pub enum BundleValidationError {
+    /// The packet at the index doesn't hold a sanitized transaction with valid signatures.
+    InvalidTransaction(usize, String),
+    /// The transaction at the index also appears earlier in the bundle.
+    DuplicateTransaction(usize),
+    /// The transaction at the index looks up an address that isn't in the table.
+    UnresolvedLookup(usize, String),
+    /// Lookup tables couldn't be fetched.
+    Rpc(String),
+    /// The verification task didn't complete.
+    Internal(String),
+}
+
+impl BundleValidationError {
+    /// Label for the rejected bundles metric.
+    pub fn label(&self) -> &'static str {
+        match self {
+            BundleValidationError::InvalidTransaction(..) => "invalid_transaction",
+            BundleValidationError::DuplicateTransaction(_) => "duplicate_transaction",
+            BundleValidationError::UnresolvedLookup(..) => "unresolved_lookup",
+            BundleValidationError::Rpc(_) => "lookup_rpc_error",
+            BundleValidationError::Internal(_) => "validation_error",
+        }
+    }
+}
+
+impl fmt::Display for BundleValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BundleValidationError::InvalidTransaction(index, e) => {
+                write!(f, "transaction {} is invalid: {}", index, e)
+            }
+            BundleValidationError::DuplicateTransaction(index) => {
+                write!(f, "transaction {} is a duplicate", index)
+            }
+            BundleValidationError::UnresolvedLookup(index, e) => {
+                write!(f, "transaction {} has an unresolved lookup: {}", index, e)
+            }
+            BundleValidationError::Rpc(e) => write!(f, "error fetching lookup tables: {}", e),
+            BundleValidationError::Internal(e) => write!(f, "error validating bundle: {}", e),
+        }
+    }
+}
+
+/// Where lookup table accounts come from. The RPC client in production; a stub in tests.
+#[tonic::async_trait]
+trait AccountFetcher: Send + Sync {
+    async fn get_multiple_accounts(&self, keys: &[Pubkey]) -> Result<Vec<Option<Account>>, String>;
+}
+
+#[tonic::async_trait]
+impl AccountFetcher for RpcClient {
+    async fn get_multiple_accounts(&self, keys: &[Pubkey]) -> Result<Vec<Option<Account>>, String> {
+        RpcClient::get_multiple_accounts(self, keys)
+            .await
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Checks that every packet of a bundle holds a valid legacy or v0 transaction. Signatures are
+/// verified on the blocking thread pool so a burst of bundles doesn't stall the async workers.
+/// With an RPC client, it also checks that every address table lookup of v0 transactions
+/// resolves.
+#[derive(Clone)]
+pub struct BundleValidator {
+    account_fetcher: Option<Arc<dyn AccountFetcher>>,
+    lookup_tables: Arc<Mutex<LookupTableCache>>,
+}
+
+struct LookupTableCache {
+    /// Lookup table address to its number of addresses, or None if the account isn't a lookup
+    /// table, and when that was fetched.
+    lengths: HashMap<Pubkey, (Option<usize>, Instant)>,
+    last_pruned: Instant,
+}
+
+impl LookupTableCache {
+    fn is_fresh(len: &Option<usize>, fetched_at: &Instant) -> bool {
+        let ttl = match len {
+            Some(_) => LOOKUP_TABLE_TTL,
+            None => NOT_A_TABLE_TTL,
+        };
+        fetched_at.elapsed() < ttl
+    }
+}
+
+impl BundleValidator {
+    pub fn new(rpc_client: Option<RpcClient>) -> Self {
+        Self::with_account_fetcher(
+            rpc_client.map(|rpc_client| Arc::new(rpc_client) as Arc<dyn AccountFetcher>),
+        )
+    }
+
+    fn with_account_fetcher(account_fetcher: Option<Arc<dyn AccountFetcher>>) -> Self {
+        BundleValidator {
+            account_fetcher,
+            lookup_tables: Arc::new(Mutex::new(LookupTableCache {
+                lengths: HashMap::new(),
+                last_pruned: Instant::now(),
+            })),
+        }
+    }
+
+    pub async fn validate(&self, bundle: &Bundle) -> Result<(), BundleValidationError> {
+        let packets = bundle.packets.clone();
+        let transactions = spawn_blocking(move || verify_transactions(&packets))
+            .await
+            .map_err(|e| BundleValidationError::Internal(e.to_string()))??;
+
+        match &self.account_fetcher {
+            Some(account_fetcher) => {
+                self.resolve_lookups(account_fetcher.as_ref(), &transactions)
+                    .await
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Checks every index of every address table lookup against the table's length.
+    async fn resolve_lookups(
+        &self,
+        account_fetcher: &dyn AccountFetcher,
+        transactions: &[SanitizedVersionedTransaction],
+    ) -> Result<(), BundleValidationError> {
+        let lookups: Vec<_> = transactions
+            .iter()
+            .enumerate()
+            .flat_map(|(index, transaction)| {
+                transaction
+                    .get_message()
+                    .message
+                    .address_table_lookups()
+                    .unwrap_or_default()
+                    .iter()
+                    .map(move |lookup| (index, lookup))
+            })
+            .collect();
+        if lookups.is_empty() {
+            return Ok(());
+        }
+
+        // fetch tables that aren't cached, expired, or too short for an index
+        let mut lengths = self.cached_lengths(lookups.iter().map(|(_, lookup)| lookup.account_key));
+        let stale: HashSet<Pubkey> = lookups
+            .iter()
+            .filter(|(_, lookup)| match lengths.get(&lookup.account_key) {
+                Some(Some(len)) => max_index(lookup).map_or(false, |max| max as usize >= *len),
+                Some(None) => false,
+                None => true,
+            })
+            .map(|(_, lookup)| lookup.account_key)
+            .collect();
+        if !stale.is_empty() {
+            let fetched = self.fetch_lengths(account_fetcher, stale).await?;
+            lengths.extend(fetched);
+        }
+
+        for (index, lookup) in lookups {
+            let len = match lengths.get(&lookup.account_key) {
+                Some(Some(len)) => *len,
+                _ => {
+                    return Err(BundleValidationError::UnresolvedLookup(
+                        index,
+                        format!("{} isn't a lookup table", lookup.account_key),
+                    ))
+                }
+            };
+            if let Some(max) = max_index(lookup).filter(|max| *max as usize >= len) {
+                return Err(BundleValidationError::UnresolvedLookup(
+                    index,
+                    format!(
+                        "index {} out of range for {} with {} addresses",
+                        max, lookup.account_key, len
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Cached lengths of the tables that haven't expired, with None for accounts known not to be
+    /// lookup tables.
+    fn cached_lengths(&self, keys: impl Iterator<Item = Pubkey>) -> HashMap<Pubkey, Option<usize>> {
+        let mut cache = self.lookup_tables.lock().unwrap();
+        if cache.last_pruned.elapsed() > LOOKUP_TABLE_TTL {
+            cache
+                .lengths
+                .retain(|_, (len, fetched_at)| LookupTableCache::is_fresh(len, fetched_at));
+            cache.last_pruned = Instant::now();
+        }
+        keys.filter_map(|key| match cache.lengths.get(&key) {
+            Some((len, fetched_at)) if LookupTableCache::is_fresh(len, fetched_at) => {
+                Some((key, *len))
+            }
+            _ => None,
+        })
+        .collect()
+    }
+
+    /// Fetches and caches the lengths of the tables, with None for accounts that don't exist or
+    /// aren't lookup tables.
+    async fn fetch_lengths(
+        &self,
+        account_fetcher: &dyn AccountFetcher,
+        keys: HashSet<Pubkey>,
+    ) -> Result<HashMap<Pubkey, Option<usize>>, BundleValidationError> {
+        let keys: Vec<Pubkey> = keys.into_iter().collect();
+        let mut lengths = HashMap::new();
+        for chunk in keys.chunks(MAX_ACCOUNTS_PER_REQUEST) {
+            let accounts = timeout(
+                LOOKUP_RPC_TIMEOUT,
+                account_fetcher.get_multiple_accounts(chunk),
+            )
+            .await
+            .map_err(|_| BundleValidationError::Rpc("request timed out".to_string()))?
+            .map_err(BundleValidationError::Rpc)?;
+            for (key, account) in chunk.iter().zip(accounts) {
+                let len = match account {
+                    Some(account) if account.owner == solana_address_lookup_table_program::id() => {
+                        match AddressLookupTable::deserialize(&account.data) {
+                            Ok(table) => Some(table.addresses.len()),
+                            Err(e) => {
+                                debug!("error deserializing lookup table {}: {}", key, e);
+                                None
+                            }
+                        }
+                    }
+                    _ => None,
+                };
+                lengths.insert(*key, len);
+            }
+        }
+
+        let now = Instant::now();
+        let mut cache = self.lookup_tables.lock().unwrap();
+        for (key, len) in &lengths {
+            cache.lengths.insert(*key, (*len, now));
+        }
+        Ok(lengths)
+    }
+}
+
+/// Deserializes and sanitizes every packet's transaction, verifies its signatures and checks that
+/// no transaction appears twice.
+fn verify_transactions(
+    packets: &[Packet],
+) -> Result<Vec<SanitizedVersionedTransaction>, BundleValidationError> {
+    let mut signatures = HashSet::new();
+    let mut transactions = Vec::with_capacity(packets.len());
+    for (index, packet) in packets.iter().enumerate() {
+        let transaction = versioned_tx_from_packet(packet)
+            .map_err(|e| BundleValidationError::InvalidTransaction(index, e.to_string()))?;
+        if !transaction.verify_with_results().iter().all(|valid| *valid) {
+            return Err(BundleValidationError::InvalidTransaction(
+                index,
+                "signature verification failed".to_string(),
+            ));
+        }
+        if let Some(signature) = transaction.signatures.first() {
+            if !signatures.insert(*signature) {
+                return Err(BundleValidationError::DuplicateTransaction(index));
+            }
+        }
+        let transaction = SanitizedVersionedTransaction::try_from(transaction)
+            .map_err(|e| BundleValidationError::InvalidTransaction(index, e.to_string()))?;
+        transactions.push(transaction);
+    }
+    Ok(transactions)
+}
+
+/// The highest index the lookup reads from its table.
+fn max_index(lookup: &MessageAddressTableLookup) -> Option<u8> {
+    lookup
+        .writable_indexes
+        .iter()
+        .chain(&lookup.readonly_indexes)
+        .max()
+        .copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jito_protos::proto_packet_from_versioned_tx;
+    use solana_address_lookup_table_program::state::LookupTableMeta;
+    use solana_sdk::hash::Hash;
+    use solana_sdk::instruction::CompiledInstruction;
+    use solana_sdk::message::{v0, MessageHeader, VersionedMessage};
+    use solana_sdk::signature::{Keypair, Signature, Signer};
+    use solana_sdk::system_program;
+    use solana_sdk::system_transaction;
+    use solana_sdk::transaction::VersionedTransaction;
+    use std::borrow::Cow;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Default)]
+    struct StubAccounts {
+        accounts: HashMap<Pubkey, Account>,
+        requests: AtomicUsize,
+    }
+
+    #[tonic::async_trait]
+    impl AccountFetcher for StubAccounts {
+        async fn get_multiple_accounts(
+            &self,
+            keys: &[Pubkey],
+        ) -> Result<Vec<Option<Account>>, String> {
+            self.requests.fetch_add(1, Ordering::Relaxed);
+            Ok(keys
+                .iter()
+                .map(|key| self.accounts.get(key).cloned())
+                .collect())
+        }
+    }
+
+    fn packet(transaction: &VersionedTransaction) -> Packet {
+        proto_packet_from_versioned_tx(transaction).unwrap()
+    }
+
+    fn transfer() -> VersionedTransaction {
+        VersionedTransaction::from(system_transaction::transfer(
+            &Keypair::new(),
+            &Pubkey::new_unique(),
+            1,
+            Hash::new_unique(),
+        ))
+    }
+
+    /// A v0 transaction reading index 3 of the lookup table.
+    fn v0_transfer(lookup_table: Pubkey) -> VersionedTransaction {
+        let payer = Keypair::new();
+        let message = VersionedMessage::V0(v0::Message {
+            header: MessageHeader {
+                num_required_signatures: 1,
+                num_readonly_signed_accounts: 0,
+                num_readonly_unsigned_accounts: 1,
+            },
+            account_keys: vec![payer.pubkey(), system_program::id()],
+            recent_blockhash: Hash::new_unique(),
+            instructions: vec![CompiledInstruction {
+                program_id_index: 1,
+                accounts: vec![0, 2],
+                data: vec![],
+            }],
+            address_table_lookups: vec![MessageAddressTableLookup {
+                account_key: lookup_table,
+                writable_indexes: vec![3],
+                readonly_indexes: vec![],
+            }],
+        });
+        VersionedTransaction::try_new(message, &[&payer]).unwrap()
+    }
+
+    fn lookup_table_account(num_addresses: usize) -> Account {
+        let table = AddressLookupTable {
+            meta: LookupTableMeta::new(Pubkey::new_unique()),
+            addresses: Cow::Owned((0..num_addresses).map(|_| Pubkey::new_unique()).collect()),
+        };
+        Account {
+            lamports: 1,
+            data: table.serialize_for_tests().unwrap(),
+            owner: solana_address_lookup_table_program::id(),
+            executable: false,
+            rent_epoch: 0,
+        }
+    }
+
+    fn cached_length(validator: &BundleValidator, key: &Pubkey) -> Option<Option<usize>> {
+        let cache = validator.lookup_tables.lock().unwrap();
+        cache.lengths.get(key).map(|(len, _)| *len)
+    }
+
+    #[test]
+    fn test_rejects_duplicate_signature() {
+        let transaction = transfer();
+        let packets = [
+            packet(&transfer()),
+            packet(&transaction),
+            packet(&transaction),
+        ];
+        assert!(matches!(
+            verify_transactions(&packets),
+            Err(BundleValidationError::DuplicateTransaction(2))
+        ));
+    }
+
+    #[test]
+    fn test_rejects_bad_signature() {
+        let mut transaction = transfer();
+        transaction.signatures[0] = Signature::new_unique();
+        let packets = [packet(&transfer()), packet(&transaction)];
+        assert!(matches!(
+            verify_transactions(&packets),
+            Err(BundleValidationError::InvalidTransaction(1, _))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_resolves_lookup_table() {
+        let table = Pubkey::new_unique();
+        let accounts = Arc::new(StubAccounts {
+            accounts: HashMap::from([(table, lookup_table_account(4))]),
+            ..StubAccounts::default()
+        });
+        let validator = BundleValidator::with_account_fetcher(Some(accounts.clone()));
+        let bundle = Bundle {
+            packets: vec![packet(&v0_transfer(table))],
+            ..Bundle::default()
+        };
+
+        validator.validate(&bundle).await.unwrap();
+        validator.validate(&bundle).await.unwrap();
+        assert_eq!(accounts.requests.load(Ordering::Relaxed), 1);
+        assert_eq!(cached_length(&validator, &table), Some(Some(4)));
+
+        // an index past the table's length is rejected
+        let accounts = Arc::new(StubAccounts {
+            accounts: HashMap::from([(table, lookup_table_account(3))]),
+            ..StubAccounts::default()
+        });
+        let validator = BundleValidator::with_account_fetcher(Some(accounts));
+        assert!(matches!(
+            validator.validate(&bundle).await,
+            Err(BundleValidationError::UnresolvedLookup(0, _))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_caches_account_that_is_not_a_table() {
+        let not_a_table = Pubkey::new_unique();
+        let accounts = Arc::new(StubAccounts {
+            accounts: HashMap::from([(
+                not_a_table,
+                Account {
+                    lamports: 1,
+                    owner: system_program::id(),
+                    ..Account::default()
+                },
+            )]),
+            ..StubAccounts::default()
+        });
+        let validator = BundleValidator::with_account_fetcher(Some(accounts.clone()));
+        let bundle = Bundle {
+            packets: vec![packet(&v0_transfer(not_a_table))],
+            ..Bundle::default()
+        };
+
+        for _ in 0..2 {
+            assert!(matches!(
+                validator.validate(&bundle).await,
+                Err(BundleValidationError::UnresolvedLookup(0, _))
+            ));
+        }
+        assert_eq!(accounts.requests.load(Ordering::Relaxed), 1);
+        assert_eq!(cached_length(&validator, &not_a_table), Some(None));
+
+        // it's fetched again once the entry expires
+        validator
+            .lookup_tables
+            .lock()
+            .unwrap()
+            .lengths
+            .get_mut(&not_a_table)
+            .unwrap()
+            .1 -= NOT_A_TABLE_TTL;
+        assert!(validator.validate(&bundle).await.is_err());
+        assert_eq!(accounts.requests.load(Ordering::Relaxed), 2);
+    }
+}
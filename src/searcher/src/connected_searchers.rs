@@ -0,0 +1,132 @@
+use crate::metrics::CONNECTED_SEARCHERS;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A searcher that sent a request within [ConnectedSearchers::IDLE_TIMEOUT].
+#[derive(Clone)]
+pub struct ConnectedSearcher {
+    pub addr: SocketAddr,
+    pub identity: Option<Pubkey>,
+    pub first_seen: Instant,
+    pub last_seen: Instant,
+}
+
+struct Searchers {
+    by_addr: HashMap<SocketAddr, ConnectedSearcher>,
+    last_pruned: Instant,
+}
+
+/// Tracks searchers by connection address. Searchers use unary requests, so a searcher counts as
+/// connected until it has been idle for [ConnectedSearchers::IDLE_TIMEOUT].
+#[derive(Clone)]
+pub struct ConnectedSearchers {
+    searchers: Arc<Mutex<Searchers>>,
+}
+
+impl Default for ConnectedSearchers {
+    fn default() -> Self {
+        ConnectedSearchers {
+            searchers: Arc::new(Mutex::new(Searchers {
+                by_addr: HashMap::default(),
+                last_pruned: Instant::now(),
+            })),
+        }
+    }
+}
+
+impl ConnectedSearchers {
+    pub const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+    /// How often idle searchers are removed when recording requests. Pruning walks every
+    /// searcher, so it isn't done on each bundle.
+    const PRUNE_INTERVAL: Duration = Duration::from_secs(5);
+
+    pub fn record(&self, addr: Option<SocketAddr>, identity: Option<Pubkey>) {
+        let now = Instant::now();
+        let mut searchers = self.searchers.lock().unwrap();
+        if let Some(addr) = addr {
+            let searcher = searchers.by_addr.entry(addr).or_insert_with(|| {
+                CONNECTED_SEARCHERS.inc();
+                ConnectedSearcher {
+                    addr,
+                    identity,
+                    first_seen: now,
+                    last_seen: now,
+                }
+            });
+            searcher.identity = identity;
+            searcher.last_seen = now;
+        }
+        if now.saturating_duration_since(searchers.last_pruned) >= Self::PRUNE_INTERVAL {
+            Self::prune(&mut searchers, now);
+        }
+    }
+
+    pub fn list(&self) -> Vec<ConnectedSearcher> {
+        let mut searchers = self.searchers.lock().unwrap();
+        Self::prune(&mut searchers, Instant::now());
+        searchers.by_addr.values().cloned().collect()
+    }
+
+    fn prune(searchers: &mut Searchers, now: Instant) {
+        searchers.by_addr.retain(|_, searcher| {
+            now.saturating_duration_since(searcher.last_seen) < Self::IDLE_TIMEOUT
+        });
+        searchers.last_pruned = now;
+        CONNECTED_SEARCHERS.set(searchers.by_addr.len() as i64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    #[test]
+    fn test_record_prunes_on_interval() {
+        let connected_searchers = ConnectedSearchers::default();
+        let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1234);
+        connected_searchers.record(Some(addr), None);
+
+        let idle_since = Instant::now() - ConnectedSearchers::IDLE_TIMEOUT;
+        {
+            let mut searchers = connected_searchers.searchers.lock().unwrap();
+            searchers.by_addr.get_mut(&addr).unwrap().last_seen = idle_since;
+        }
+        // recording another searcher before the prune interval leaves the idle one in place
+        let other = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 5678);
+        connected_searchers.record(Some(other), Some(Pubkey::new_unique()));
+        assert_eq!(
+            connected_searchers.searchers.lock().unwrap().by_addr.len(),
+            2
+        );
+
+        connected_searchers.searchers.lock().unwrap().last_pruned -=
+            ConnectedSearchers::PRUNE_INTERVAL;
+        connected_searchers.record(Some(other), None);
+        let searchers = connected_searchers.searchers.lock().unwrap();
+        assert!(!searchers.by_addr.contains_key(&addr));
+        assert!(searchers.by_addr.contains_key(&other));
+    }
+
+    #[test]
+    fn test_list_skips_idle_searchers() {
+        let connected_searchers = ConnectedSearchers::default();
+        let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1234);
+        connected_searchers.record(Some(addr), None);
+        assert_eq!(connected_searchers.list().len(), 1);
+
+        connected_searchers
+            .searchers
+            .lock()
+            .unwrap()
+            .by_addr
+            .get_mut(&addr)
+            .unwrap()
+            .last_seen -= ConnectedSearchers::IDLE_TIMEOUT;
+        assert!(connected_searchers.list().is_empty());
+    }
+}
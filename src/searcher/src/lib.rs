@@ -1 +1,6 @@
+pub mod bundle_status;
+pub mod bundle_validator;
+pub mod connected_searchers;
+pub mod metrics;
+pub mod rate_limiter;
 pub mod server;
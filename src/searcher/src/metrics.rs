@@ -0,0 +1,56 @@
+use lazy_static::lazy_static;
+use prometheus::{
+    register_gauge_vec, register_int_counter, register_int_counter_vec, register_int_gauge,
+    register_int_gauge_vec, GaugeVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec,
+};
+
+lazy_static! {
+    /// Bundle requests from `send_bundle` and `stream_bundles`, counted before any check, so
+    /// requests rejected for their role, rate limit or shape are included and received minus
+    /// rejected is what was forwarded.
+    pub static ref BUNDLES_RECEIVED: IntCounter = register_int_counter!(
+        "block_engine_bundles_received_total",
+        "Bundle requests received from searchers, including rejected ones"
+    )
+    .unwrap();
+
+    /// Bundles that were not forwarded to validators, by reason.
+    pub static ref BUNDLES_REJECTED: IntCounterVec = register_int_counter_vec!(
+        "block_engine_bundles_rejected_total",
+        "Bundles received from searchers that were not forwarded",
+        &["reason"]
+    )
+    .unwrap();
+
+    /// Bundles rejected by the rate limiter, by whether the searcher was keyed by pubkey or IP.
+    pub static ref RATE_LIMITED: IntCounterVec = register_int_counter_vec!(
+        "block_engine_searcher_rate_limited_total",
+        "Bundles rejected for exceeding a searcher's rate limit",
+        &["key"]
+    )
+    .unwrap();
+
+    /// Rate limit buckets being tracked, by key kind. A bucket is dropped once it has refilled.
+    pub static ref RATE_LIMIT_BUCKETS: IntGaugeVec = register_int_gauge_vec!(
+        "block_engine_searcher_rate_limit_buckets",
+        "Searchers with a partly used rate limit bucket",
+        &["key"]
+    )
+    .unwrap();
+
+    /// Tokens taken from rate limit buckets and not yet refilled, summed by key kind. Updated on
+    /// each request and prune, so refills of idle buckets show up within a minute.
+    pub static ref RATE_LIMIT_TOKENS_IN_USE: GaugeVec = register_gauge_vec!(
+        "block_engine_searcher_rate_limit_tokens_in_use",
+        "Tokens taken from searchers' rate limit buckets and not yet refilled",
+        &["key"]
+    )
+    .unwrap();
+
+    /// Searchers that sent a request within the idle timeout.
+    pub static ref CONNECTED_SEARCHERS: IntGauge = register_int_gauge!(
+        "block_engine_connected_searchers",
+        "Searchers that sent a request recently"
+    )
+    .unwrap();
+}
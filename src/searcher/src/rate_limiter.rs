@@ -0,0 +1,209 @@
+use crate::metrics::{RATE_LIMITED, RATE_LIMIT_BUCKETS, RATE_LIMIT_TOKENS_IN_USE};
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Who a request counts against: the authenticated searcher, or its IP when unauthenticated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RateLimitKey {
+    Pubkey(Pubkey),
+    Ip(IpAddr),
+}
+
+impl RateLimitKey {
+    /// Label for the kind of key, used in metrics.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            RateLimitKey::Pubkey(_) => "pubkey",
+            RateLimitKey::Ip(_) => "ip",
+        }
+    }
+}
+
+impl fmt::Display for RateLimitKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RateLimitKey::Pubkey(pubkey) => write!(f, "{}", pubkey),
+            RateLimitKey::Ip(ip) => write!(f, "{}", ip),
+        }
+    }
+}
+
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+    /// Usage last added to [RATE_LIMIT_TOKENS_IN_USE].
+    reported_usage: f64,
+}
+
+struct Buckets {
+    buckets: HashMap<RateLimitKey, TokenBucket>,
+    last_pruned: Instant,
+}
+
+/// Token-bucket rate limits, one bucket per [RateLimitKey]. Each bucket holds up to `burst`
+/// tokens and refills at `rate_per_sec`; a request takes one token.
+#[derive(Clone)]
+pub struct RateLimiter {
+    rate_per_sec: f64,
+    burst: u32,
+    buckets: Arc<Mutex<Buckets>>,
+}
+
+impl RateLimiter {
+    /// How often buckets that have refilled are removed.
+    const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+    pub fn new(rate_per_sec: f64, burst: u32) -> Self {
+        assert!(rate_per_sec > 0.0, "rate limit must be positive");
+        assert!(burst > 0, "rate limit burst must be positive");
+        RateLimiter {
+            rate_per_sec,
+            burst,
+            buckets: Arc::new(Mutex::new(Buckets {
+                buckets: HashMap::new(),
+                last_pruned: Instant::now(),
+            })),
+        }
+    }
+
+    pub fn rate_per_sec(&self) -> f64 {
+        self.rate_per_sec
+    }
+
+    pub fn burst(&self) -> u32 {
+        self.burst
+    }
+
+    /// Takes a token from the key's bucket. Returns false if the bucket is empty.
+    pub fn try_acquire(&self, key: RateLimitKey) -> bool {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        if now.saturating_duration_since(buckets.last_pruned) >= Self::PRUNE_INTERVAL {
+            self.prune(&mut buckets, now);
+        }
+        let burst = self.burst as f64;
+        let bucket = buckets.buckets.entry(key).or_insert_with(|| {
+            RATE_LIMIT_BUCKETS.with_label_values(&[key.kind()]).inc();
+            TokenBucket {
+                tokens: burst,
+                last_refill: now,
+                reported_usage: 0.0,
+            }
+        });
+        self.refill(bucket, now);
+
+        let acquired = bucket.tokens >= 1.0;
+        if acquired {
+            bucket.tokens -= 1.0;
+        } else {
+            RATE_LIMITED.with_label_values(&[key.kind()]).inc();
+        }
+        Self::report_usage(&key, bucket, burst - bucket.tokens);
+        acquired
+    }
+
+    /// Tokens currently taken from the key's bucket, out of [RateLimiter::burst]. Served by the
+    /// admin API; the metrics only have totals by key kind.
+    pub fn usage(&self, key: &RateLimitKey) -> f64 {
+        let mut buckets = self.buckets.lock().unwrap();
+        match buckets.buckets.get_mut(key) {
+            Some(bucket) => {
+                self.refill(bucket, Instant::now());
+                self.burst as f64 - bucket.tokens
+            }
+            None => 0.0,
+        }
+    }
+
+    /// Removes buckets that have refilled so idle searchers don't accumulate.
+    fn prune(&self, buckets: &mut Buckets, now: Instant) {
+        let burst = self.burst as f64;
+        buckets.buckets.retain(|key, bucket| {
+            self.refill(bucket, now);
+            Self::report_usage(key, bucket, burst - bucket.tokens);
+            let refilled = bucket.tokens >= burst;
+            if refilled {
+                RATE_LIMIT_BUCKETS.with_label_values(&[key.kind()]).dec();
+            }
+            !refilled
+        });
+        buckets.last_pruned = now;
+    }
+
+    /// Moves the key kind's [RATE_LIMIT_TOKENS_IN_USE] by the change in the bucket's usage.
+    fn report_usage(key: &RateLimitKey, bucket: &mut TokenBucket, usage: f64) {
+        RATE_LIMIT_TOKENS_IN_USE
+            .with_label_values(&[key.kind()])
+            .add(usage - bucket.reported_usage);
+        bucket.reported_usage = usage;
+    }
+
+    fn refill(&self, bucket: &mut TokenBucket, now: Instant) {
+        let elapsed = now.saturating_duration_since(bucket.last_refill);
+        bucket.tokens =
+            (bucket.tokens + elapsed.as_secs_f64() * self.rate_per_sec).min(self.burst as f64);
+        bucket.last_refill = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn test_bucket_allows_burst_then_limits() {
+        let rate_limiter = RateLimiter::new(0.001, 2);
+        let key = RateLimitKey::Pubkey(Pubkey::new_unique());
+        assert!(rate_limiter.try_acquire(key));
+        assert!(rate_limiter.try_acquire(key));
+        assert!(!rate_limiter.try_acquire(key));
+        assert!((rate_limiter.usage(&key) - 2.0).abs() < 0.01);
+
+        // other keys have their own bucket
+        let ip = RateLimitKey::Ip(IpAddr::V4(Ipv4Addr::LOCALHOST));
+        assert_eq!(rate_limiter.usage(&ip), 0.0);
+        assert!(rate_limiter.try_acquire(ip));
+    }
+
+    #[test]
+    fn test_bucket_refills() {
+        let rate_limiter = RateLimiter::new(1.0, 2);
+        let mut bucket = TokenBucket {
+            tokens: 0.0,
+            last_refill: Instant::now(),
+            reported_usage: 0.0,
+        };
+        rate_limiter.refill(
+            &mut bucket,
+            bucket.last_refill + Duration::from_millis(1_500),
+        );
+        assert!((bucket.tokens - 1.5).abs() < f64::EPSILON);
+        // never above the burst
+        rate_limiter.refill(&mut bucket, bucket.last_refill + Duration::from_secs(10));
+        assert!((bucket.tokens - 2.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_prune_removes_refilled_buckets() {
+        let rate_limiter = RateLimiter::new(1.0, 10);
+        let busy = RateLimitKey::Pubkey(Pubkey::new_unique());
+        let idle = RateLimitKey::Pubkey(Pubkey::new_unique());
+        for _ in 0..10 {
+            assert!(rate_limiter.try_acquire(busy));
+        }
+        assert!(rate_limiter.try_acquire(idle));
+
+        // after 5s the idle bucket has refilled and the busy one is still half empty
+        let mut buckets = rate_limiter.buckets.lock().unwrap();
+        let now = Instant::now() + Duration::from_secs(5);
+        rate_limiter.prune(&mut buckets, now);
+        assert!(buckets.buckets.contains_key(&busy));
+        assert!(!buckets.buckets.contains_key(&idle));
+        assert_eq!(buckets.last_pruned, now);
+    }
+}
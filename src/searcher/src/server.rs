@@ -1,26 +1,172 @@
-use jito_protos::bundle::BundleUuid;
+use crate::bundle_status::bundle_status;
+use crate::bundle_validator::{BundleValidationError, BundleValidator};
+use crate::connected_searchers::ConnectedSearchers;
+use crate::metrics::{BUNDLES_RECEIVED, BUNDLES_REJECTED};
+use crate::rate_limiter::{RateLimitKey, RateLimiter};
+use jito_auth::interceptor::authenticated_identity;
+use jito_auth::token::AuthenticatedIdentity;
+use jito_leader_schedule::tracker::LeaderScheduleTracker;
+use jito_protos::auth::Role;
+use jito_protos::bundle::{Bundle, BundleUuid};
 use jito_protos::searcher::{
     searcher_service_server::SearcherService, ConnectedLeadersRequest, ConnectedLeadersResponse,
     GetTipAccountsRequest, GetTipAccountsResponse, NextScheduledLeaderRequest,
     NextScheduledLeaderResponse, PendingTxNotification, PendingTxSubscriptionRequest,
-    SendBundleRequest, SendBundleResponse,
+    SendBundleRequest, SendBundleResponse, SlotList,
 };
-use log::info;
-use tokio::sync::mpsc::Sender;
+use jito_protos::searcher_ext::{
+    searcher_ext_service_server::SearcherExtService, stream_bundles_response::Outcome,
+    BundleRejection, BundleStatus, StreamBundlesRequest, StreamBundlesResponse,
+    SubscribeBundleStatusesRequest,
+};
+use jito_validator::forwarder::Forwarder;
+use log::{debug, info};
+use solana_sdk::pubkey::Pubkey;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::sync::mpsc::{channel, Sender};
+use tokio::sync::watch;
+use tokio::time::interval;
 use tokio_stream::wrappers::ReceiverStream;
-use tonic::{Request, Response, Status};
+use tonic::{Request, Response, Status, Streaming};
 use uuid::Uuid;
 
+/// Responses a bundle stream buffers before it stops reading bundles from the searcher.
+const STREAM_RESPONSE_CAPACITY: usize = 100;
+
+/// Most bundles one status subscription may follow.
+const MAX_BUNDLE_STATUS_UUIDS: usize = 100;
+
+/// How often a status subscription checks its bundles for changes.
+const BUNDLE_STATUS_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Clone)]
 pub struct SearcherServiceImpl {
     bundle_sender: Sender<BundleUuid>,
+    shutdown_receiver: watch::Receiver<bool>,
+    connected_searchers: ConnectedSearchers,
+    rate_limiter: Option<RateLimiter>,
+    forwarder: Forwarder,
+    leader_schedule: Option<LeaderScheduleTracker>,
+    tip_accounts: Vec<Pubkey>,
+    bundle_validator: BundleValidator,
 }
 
 impl SearcherServiceImpl {
     pub const MAX_BUNDLE_LEN: usize = 5;
 
     #[allow(clippy::too_many_arguments)]
-    pub fn new(bundle_sender: Sender<BundleUuid>) -> Self {
-        SearcherServiceImpl { bundle_sender }
+    pub fn new(
+        bundle_sender: Sender<BundleUuid>,
+        shutdown_receiver: watch::Receiver<bool>,
+        connected_searchers: ConnectedSearchers,
+        rate_limiter: Option<RateLimiter>,
+        forwarder: Forwarder,
+        leader_schedule: Option<LeaderScheduleTracker>,
+        tip_accounts: Vec<Pubkey>,
+        bundle_validator: BundleValidator,
+    ) -> Self {
+        SearcherServiceImpl {
+            bundle_sender,
+            shutdown_receiver,
+            connected_searchers,
+            rate_limiter,
+            forwarder,
+            leader_schedule,
+            tip_accounts,
+            bundle_validator,
+        }
+    }
+
+    fn leader_schedule(&self) -> Result<&LeaderScheduleTracker, Status> {
+        self.leader_schedule.as_ref().ok_or_else(|| {
+            Status::failed_precondition("block engine isn't following the leader schedule")
+        })
+    }
+
+    /// Validates a bundle and forwards it to validators, returning its uuid. Shared by
+    /// `send_bundle` and `stream_bundles`.
+    async fn process_bundle(
+        &self,
+        identity: Option<AuthenticatedIdentity>,
+        remote_addr: Option<SocketAddr>,
+        bundle: Option<Bundle>,
+    ) -> Result<String, Status> {
+        BUNDLES_RECEIVED.inc();
+        if matches!(identity, Some(identity) if identity.role != Role::Searcher) {
+            BUNDLES_REJECTED.with_label_values(&["wrong_role"]).inc();
+            return Err(Status::permission_denied("only searchers may send bundles"));
+        }
+        self.connected_searchers
+            .record(remote_addr, identity.map(|identity| identity.pubkey));
+
+        if *self.shutdown_receiver.borrow() {
+            BUNDLES_REJECTED.with_label_values(&["shutting_down"]).inc();
+            return Err(Status::unavailable("block engine shutting down"));
+        }
+
+        let bundle = match bundle {
+            Some(bundle) => bundle,
+            None => {
+                BUNDLES_REJECTED
+                    .with_label_values(&["missing_bundle"])
+                    .inc();
+                return Err(Status::invalid_argument("missing bundle"));
+            }
+        };
+        if bundle.packets.is_empty() {
+            BUNDLES_REJECTED.with_label_values(&["empty_bundle"]).inc();
+            return Err(Status::invalid_argument("bundle has no transactions"));
+        }
+        if bundle.packets.len() > Self::MAX_BUNDLE_LEN {
+            BUNDLES_REJECTED.with_label_values(&["too_long"]).inc();
+            return Err(Status::invalid_argument(format!(
+                "bundle has more than {} transactions",
+                Self::MAX_BUNDLE_LEN
+            )));
+        }
+
+        if let Some(rate_limiter) = &self.rate_limiter {
+            // unauthenticated searchers share a bucket per IP
+            let key = match (identity, remote_addr) {
+                (Some(identity), _) => Some(RateLimitKey::Pubkey(identity.pubkey)),
+                (None, Some(addr)) => Some(RateLimitKey::Ip(addr.ip())),
+                (None, None) => None,
+            };
+            if let Some(key) = key {
+                if !rate_limiter.try_acquire(key) {
+                    BUNDLES_REJECTED.with_label_values(&["rate_limited"]).inc();
+                    return Err(Status::resource_exhausted(format!(
+                        "rate limit exceeded: {} bundles/s with a burst of {}",
+                        rate_limiter.rate_per_sec(),
+                        rate_limiter.burst()
+                    )));
+                }
+            }
+        }
+
+        if let Err(e) = self.bundle_validator.validate(&bundle).await {
+            BUNDLES_REJECTED.with_label_values(&[e.label()]).inc();
+            return Err(match e {
+                BundleValidationError::Rpc(_) => Status::unavailable(e.to_string()),
+                BundleValidationError::Internal(_) => Status::internal(e.to_string()),
+                _ => Status::invalid_argument(e.to_string()),
+            });
+        }
+
+        let uuid = Uuid::new_v4().to_string();
+        info!("received bundle_uuid: {:?}", uuid);
+        self.bundle_sender
+            .send(BundleUuid {
+                bundle: Some(bundle),
+                uuid: uuid.clone(),
+            })
+            .await
+            .map_err(|_| {
+                BUNDLES_REJECTED.with_label_values(&["forward_error"]).inc();
+                Status::internal("error forwarding bundle")
+            })?;
+        Ok(uuid)
     }
 }
 
@@ -32,49 +178,189 @@ impl SearcherService for SearcherServiceImpl {
         &self,
         _request: Request<PendingTxSubscriptionRequest>,
     ) -> Result<Response<Self::SubscribePendingTransactionsStream>, Status> {
-        unimplemented!()
+        Err(Status::unimplemented(
+            "the block engine doesn't stream pending transactions",
+        ))
     }
 
     async fn send_bundle(
         &self,
         request: Request<SendBundleRequest>,
     ) -> Result<Response<SendBundleResponse>, Status> {
-        let uuid = Uuid::new_v4().to_string();
-        let bundle_uuid = BundleUuid {
-            bundle: request.into_inner().bundle,
-            uuid: uuid.clone(),
-        };
-
-        info!("received bundle_uuid: {:?}", bundle_uuid.uuid);
-
-        if bundle_uuid.bundle.is_some() {
-            self.bundle_sender
-                .send(bundle_uuid)
-                .await
-                .map_err(|_| Status::internal("error forwarding bundle"))?;
-        }
-
+        let identity = authenticated_identity(&request);
+        let remote_addr = request.remote_addr();
+        let uuid = self
+            .process_bundle(identity, remote_addr, request.into_inner().bundle)
+            .await?;
         Ok(Response::new(SendBundleResponse { uuid }))
     }
 
+    /// The next slot led by a validator connected to the block engine.
     async fn get_next_scheduled_leader(
         &self,
         _request: Request<NextScheduledLeaderRequest>,
     ) -> Result<Response<NextScheduledLeaderResponse>, Status> {
-        unimplemented!()
+        let leader_schedule = self.leader_schedule()?;
+        let current_slot = leader_schedule
+            .current_slot()
+            .ok_or_else(|| Status::unavailable("leader schedule not loaded yet"))?;
+        let (next_leader_slot, next_leader_identity) = leader_schedule
+            .next_leader_slot(&self.forwarder.connected_validators())
+            .ok_or_else(|| {
+                Status::not_found("no connected validator leads a slot in the rest of the epoch")
+            })?;
+        Ok(Response::new(NextScheduledLeaderResponse {
+            current_slot,
+            next_leader_slot,
+            next_leader_identity: next_leader_identity.to_string(),
+        }))
     }
 
+    /// Validators connected to the block engine and their leader slots in the rest of the epoch.
+    /// Slots are left empty if the block engine isn't following the leader schedule.
     async fn get_connected_leaders(
         &self,
         _request: Request<ConnectedLeadersRequest>,
     ) -> Result<Response<ConnectedLeadersResponse>, Status> {
-        unimplemented!()
+        let connected_validators = self.forwarder.connected_validators();
+        let mut leader_slots = match &self.leader_schedule {
+            Some(leader_schedule) => leader_schedule.upcoming_leader_slots(&connected_validators),
+            None => Default::default(),
+        };
+        let connected_validators = connected_validators
+            .into_iter()
+            .map(|identity| {
+                let slots = leader_slots.remove(&identity).unwrap_or_default();
+                (identity.to_string(), SlotList { slots })
+            })
+            .collect();
+        Ok(Response::new(ConnectedLeadersResponse {
+            connected_validators,
+        }))
     }
 
     async fn get_tip_accounts(
         &self,
         _request: Request<GetTipAccountsRequest>,
     ) -> Result<Response<GetTipAccountsResponse>, Status> {
-        unimplemented!()
+        Ok(Response::new(GetTipAccountsResponse {
+            accounts: self
+                .tip_accounts
+                .iter()
+                .map(|account| account.to_string())
+                .collect(),
+        }))
+    }
+}
+
+#[tonic::async_trait]
+impl SearcherExtService for SearcherServiceImpl {
+    type StreamBundlesStream = ReceiverStream<Result<StreamBundlesResponse, Status>>;
+
+    async fn stream_bundles(
+        &self,
+        request: Request<Streaming<StreamBundlesRequest>>,
+    ) -> Result<Response<Self::StreamBundlesStream>, Status> {
+        if *self.shutdown_receiver.borrow() {
+            return Err(Status::unavailable("block engine shutting down"));
+        }
+        let identity = authenticated_identity(&request);
+        let remote_addr = request.remote_addr();
+        let mut bundles = request.into_inner();
+        let mut shutdown_receiver = self.shutdown_receiver.clone();
+        let (sender, receiver) = channel(STREAM_RESPONSE_CAPACITY);
+
+        let service = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let request = tokio::select! {
+                    // ends the stream so it doesn't hold up shutdown
+                    _ = shutdown_receiver.changed() => {
+                        let _ = sender
+                            .send(Err(Status::unavailable("block engine shutting down")))
+                            .await;
+                        break;
+                    }
+                    request = bundles.message() => request,
+                };
+                let request = match request {
+                    Ok(Some(request)) => request,
+                    Ok(None) => break,
+                    Err(status) => {
+                        debug!("bundle stream error from {:?}: {}", remote_addr, status);
+                        break;
+                    }
+                };
+
+                let outcome = match service
+                    .process_bundle(identity, remote_addr, request.bundle)
+                    .await
+                {
+                    Ok(uuid) => Outcome::Uuid(uuid),
+                    Err(status) => Outcome::Rejected(BundleRejection {
+                        code: status.code() as i32,
+                        message: status.message().to_string(),
+                    }),
+                };
+                let response = StreamBundlesResponse {
+                    outcome: Some(outcome),
+                };
+                if sender.send(Ok(response)).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(receiver)))
+    }
+
+    type SubscribeBundleStatusesStream = ReceiverStream<Result<BundleStatus, Status>>;
+
+    async fn subscribe_bundle_statuses(
+        &self,
+        request: Request<SubscribeBundleStatusesRequest>,
+    ) -> Result<Response<Self::SubscribeBundleStatusesStream>, Status> {
+        if *self.shutdown_receiver.borrow() {
+            return Err(Status::unavailable("block engine shutting down"));
+        }
+        let uuids = request.into_inner().uuids;
+        if uuids.is_empty() || uuids.len() > MAX_BUNDLE_STATUS_UUIDS {
+            return Err(Status::invalid_argument(format!(
+                "subscribe to between 1 and {} bundles",
+                MAX_BUNDLE_STATUS_UUIDS
+            )));
+        }
+        let mut shutdown_receiver = self.shutdown_receiver.clone();
+        let (sender, receiver) = channel(uuids.len());
+
+        let forwarder = self.forwarder.clone();
+        tokio::spawn(async move {
+            let mut statuses: Vec<Option<BundleStatus>> = vec![None; uuids.len()];
+            let mut poll_interval = interval(BUNDLE_STATUS_POLL_INTERVAL);
+            loop {
+                tokio::select! {
+                    _ = shutdown_receiver.changed() => {
+                        let _ = sender
+                            .send(Err(Status::unavailable("block engine shutting down")))
+                            .await;
+                        break;
+                    }
+                    _ = sender.closed() => break,
+                    _ = poll_interval.tick() => {}
+                }
+                for (uuid, last_status) in uuids.iter().zip(statuses.iter_mut()) {
+                    let status = bundle_status(&forwarder, uuid);
+                    if last_status.as_ref() == Some(&status) {
+                        continue;
+                    }
+                    *last_status = Some(status.clone());
+                    if sender.send(Ok(status)).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(receiver)))
     }
 }
@@ -0,0 +1,190 @@
+use jito_protos::auth::auth_service_client::AuthServiceClient;
+use jito_protos::auth::{
+    GenerateAuthChallengeRequest, GenerateAuthTokensRequest, RefreshAccessTokenRequest, Role, Token,
+};
+use log::{info, warn};
+use solana_sdk::signature::{Keypair, Signer};
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::time::sleep;
+use tonic::metadata::MetadataValue;
+use tonic::service::Interceptor;
+use tonic::transport::Channel;
+use tonic::{Code, Request, Status};
+
+/// How long before a token expires it's renewed.
+const RENEW_MARGIN: Duration = Duration::from_secs(60);
+
+/// Shortest wait between renewals, so a token without an expiry isn't renewed in a loop.
+const MIN_RENEW_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Longest wait between attempts to renew the access token while the auth service is failing.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Attaches the current access token to every request as `authorization: Bearer <token>`. The
+/// default interceptor has no token and leaves requests unauthenticated.
+#[derive(Clone, Default)]
+pub struct ClientInterceptor {
+    access_token: Option<Arc<RwLock<String>>>,
+}
+
+impl Interceptor for ClientInterceptor {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        if let Some(access_token) = &self.access_token {
+            let value =
+                MetadataValue::from_str(&format!("Bearer {}", access_token.read().unwrap()))
+                    .map_err(|_| Status::internal("access token isn't valid ascii"))?;
+            request.metadata_mut().insert("authorization", value);
+        }
+        Ok(request)
+    }
+}
+
+/// Authenticates a keypair with the block engine's auth service and keeps its access token
+/// fresh for the [ClientInterceptor]s it hands out.
+pub struct Authenticator {
+    auth_client: AuthServiceClient<Channel>,
+    keypair: Arc<Keypair>,
+    role: Role,
+    access_token: Arc<RwLock<String>>,
+    access_token_expires_at: SystemTime,
+    refresh_token: Token,
+}
+
+impl Authenticator {
+    /// Runs the challenge flow: requests a challenge, signs `<pubkey>-<challenge>` and exchanges
+    /// the signature for tokens.
+    pub async fn new(channel: Channel, keypair: Arc<Keypair>, role: Role) -> Result<Self, Status> {
+        let mut auth_client = AuthServiceClient::new(channel);
+        let (access_token, refresh_token) =
+            Self::authenticate(&mut auth_client, &keypair, role).await?;
+        Ok(Authenticator {
+            auth_client,
+            keypair,
+            role,
+            access_token_expires_at: expires_at(&access_token),
+            access_token: Arc::new(RwLock::new(access_token.value)),
+            refresh_token,
+        })
+    }
+
+    pub fn interceptor(&self) -> ClientInterceptor {
+        ClientInterceptor {
+            access_token: Some(self.access_token.clone()),
+        }
+    }
+
+    /// Renews the access token shortly before it expires, forever. Spawn it and drop the task
+    /// when the client is done.
+    pub async fn run(mut self) {
+        let mut retry_delay = MIN_RENEW_INTERVAL;
+        loop {
+            let renew_in = self
+                .access_token_expires_at
+                .duration_since(SystemTime::now())
+                .unwrap_or_default()
+                .saturating_sub(RENEW_MARGIN)
+                .max(MIN_RENEW_INTERVAL);
+            sleep(renew_in).await;
+
+            match self.renew().await {
+                Ok(()) => retry_delay = MIN_RENEW_INTERVAL,
+                Err(e) => {
+                    warn!(
+                        "error renewing access token, retrying in {:?}: {}",
+                        retry_delay, e
+                    );
+                    sleep(retry_delay).await;
+                    retry_delay = (retry_delay * 2).min(MAX_RETRY_DELAY);
+                }
+            }
+        }
+    }
+
+    /// Refreshes the access token, or authenticates again if the refresh token is about to
+    /// expire or was rejected.
+    async fn renew(&mut self) -> Result<(), Status> {
+        let refresh_expires_in = expires_at(&self.refresh_token)
+            .duration_since(SystemTime::now())
+            .unwrap_or_default();
+        if refresh_expires_in > RENEW_MARGIN {
+            let result = self
+                .auth_client
+                .refresh_access_token(RefreshAccessTokenRequest {
+                    refresh_token: self.refresh_token.value.clone(),
+                })
+                .await;
+            match result {
+                Ok(response) => {
+                    let access_token = response
+                        .into_inner()
+                        .access_token
+                        .ok_or_else(|| Status::internal("missing access token"))?;
+                    self.set_access_token(access_token);
+                    info!("refreshed access token");
+                    return Ok(());
+                }
+                Err(status) if status.code() == Code::Unauthenticated => {
+                    warn!("refresh token rejected, authenticating again");
+                }
+                Err(status) => return Err(status),
+            }
+        }
+
+        let (access_token, refresh_token) =
+            Self::authenticate(&mut self.auth_client, &self.keypair, self.role).await?;
+        self.set_access_token(access_token);
+        self.refresh_token = refresh_token;
+        info!("authenticated again");
+        Ok(())
+    }
+
+    fn set_access_token(&mut self, access_token: Token) {
+        self.access_token_expires_at = expires_at(&access_token);
+        *self.access_token.write().unwrap() = access_token.value;
+    }
+
+    async fn authenticate(
+        auth_client: &mut AuthServiceClient<Channel>,
+        keypair: &Keypair,
+        role: Role,
+    ) -> Result<(Token, Token), Status> {
+        let pubkey = keypair.pubkey();
+        let challenge = auth_client
+            .generate_auth_challenge(GenerateAuthChallengeRequest {
+                role: role as i32,
+                pubkey: pubkey.to_bytes().to_vec(),
+            })
+            .await?
+            .into_inner()
+            .challenge;
+
+        // the auth service verifies "<pubkey>-<challenge>"
+        let signature = keypair.sign_message(format!("{}-{}", pubkey, challenge).as_bytes());
+        let tokens = auth_client
+            .generate_auth_tokens(GenerateAuthTokensRequest {
+                challenge,
+                client_pubkey: pubkey.to_bytes().to_vec(),
+                signed_challenge: signature.as_ref().to_vec(),
+            })
+            .await?
+            .into_inner();
+        match (tokens.access_token, tokens.refresh_token) {
+            (Some(access_token), Some(refresh_token)) => {
+                info!("authenticated pubkey: {} role: {:?}", pubkey, role);
+                Ok((access_token, refresh_token))
+            }
+            _ => Err(Status::internal("auth service didn't return tokens")),
+        }
+    }
+}
+
+/// When the token expires; tokens without an expiry are treated as already expired.
+fn expires_at(token: &Token) -> SystemTime {
+    token
+        .expires_at_utc
+        .as_ref()
+        .map(|ts| UNIX_EPOCH + Duration::new(ts.seconds.max(0) as u64, ts.nanos.max(0) as u32))
+        .unwrap_or(UNIX_EPOCH)
+}
@@ -0,0 +1,92 @@
+use crate::funding::Funder;
+use crate::landing::{LandingTracker, SentBundle};
+use crate::lookup_table::create_lookup_table;
+use crate::output::{emit, OutputFormat};
+use crate::transfers::TransferBuilder;
+use jito_searcher_client::client::SearcherClient;
+use log::info;
+use serde_json::json;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::commitment_config::{CommitmentConfig, CommitmentLevel};
+use std::error::Error;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+
+/// How often the landing summary is printed while verifying landing.
+const LANDING_REPORT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Funds the signers, then sends bundles of transfers every millisecond until interrupted. With
+/// `verify_landing`, also checks which bundles land and periodically prints a summary.
+pub async fn blast(
+    client: &SearcherClient,
+    rpc_client: Arc<RpcClient>,
+    funder: &Funder,
+    mut transfers: TransferBuilder,
+    verify_landing: bool,
+    output: OutputFormat,
+) -> Result<(), Box<dyn Error>> {
+    funder.fund(&rpc_client, &transfers.pubkeys()).await?;
+    if transfers.wants_lookup_table() {
+        let lookup_table = create_lookup_table(
+            &rpc_client,
+            transfers.lookup_table_authority(),
+            transfers.lookup_table_addresses(),
+            funder.commitment,
+        )
+        .await?;
+        transfers.set_lookup_table(lookup_table);
+    }
+
+    let tracker = verify_landing
+        .then(|| LandingTracker::start(rpc_client.clone(), CommitmentConfig::confirmed()));
+    let mut last_report_time = Instant::now();
+
+    let mut last_blockhash_time = Instant::now();
+    let (mut blockhash, mut last_valid_block_height) = rpc_client
+        .get_latest_blockhash_with_commitment(CommitmentConfig {
+            commitment: CommitmentLevel::Processed,
+        })
+        .await?;
+
+    info!("sending bundles...");
+    loop {
+        if last_blockhash_time.elapsed() > Duration::from_secs(5) {
+            (blockhash, last_valid_block_height) = rpc_client
+                .get_latest_blockhash_with_commitment(CommitmentConfig {
+                    commitment: CommitmentLevel::Processed,
+                })
+                .await?;
+            last_blockhash_time = Instant::now();
+        }
+        let txs = transfers.build(5, None, blockhash);
+
+        let sent_at = Instant::now();
+        match client.send_bundle(&txs).await {
+            Ok(uuid) => {
+                emit(output, format!("uuid: {}", uuid), json!({ "uuid": uuid }));
+                if let Some(tracker) = &tracker {
+                    tracker.track(SentBundle::new(
+                        uuid,
+                        &txs,
+                        last_valid_block_height,
+                        sent_at,
+                    ));
+                }
+            }
+            Err(e) => emit(
+                output,
+                format!("error sending bundle: {}", e),
+                json!({ "error": e.to_string() }),
+            ),
+        }
+        if let Some(tracker) = &tracker {
+            if last_report_time.elapsed() > LANDING_REPORT_INTERVAL {
+                let (human, json) = tracker.stats().summary();
+                emit(output, human, json!({ "landing": json }));
+                last_report_time = Instant::now();
+            }
+        }
+        sleep(Duration::from_millis(1)).await;
+    }
+}
@@ -0,0 +1,352 @@
+use crate::auth::{Authenticator, ClientInterceptor};
+use jito_protos::auth::Role;
+use jito_protos::searcher::searcher_service_client::SearcherServiceClient;
+use jito_protos::searcher::{
+    ConnectedLeadersRequest, GetTipAccountsRequest, NextScheduledLeaderRequest,
+    PendingTxNotification, PendingTxSubscriptionRequest, SendBundleRequest,
+};
+use jito_protos::searcher_ext::searcher_ext_service_client::SearcherExtServiceClient;
+use jito_protos::searcher_ext::{BundleStatus, SubscribeBundleStatusesRequest};
+use jito_protos::{bundle_from_versioned_txs, ConversionError};
+use log::warn;
+use solana_sdk::clock::Slot;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Keypair;
+use solana_sdk::transaction::VersionedTransaction;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::sleep;
+use tonic::codegen::InterceptedService;
+use tonic::transport::{self, Channel};
+use tonic::{Code, Response, Status, Streaming};
+
+/// Times a failed request is retried while the channel reconnects.
+const MAX_RETRIES: u32 = 5;
+
+/// Messages tonic gives the `UNKNOWN` status it returns when the channel can't connect or the
+/// connection fails during a request.
+const TRANSPORT_ERROR_MESSAGES: &[&str] = &["transport error", "Service was not ready"];
+
+/// Wait before the first retry; doubles after each one.
+const INITIAL_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+#[derive(Debug)]
+pub enum ClientError {
+    InvalidUrl(String),
+    Transport(transport::Error),
+    Status(Status),
+    InvalidResponse(String),
+    InvalidTransaction(ConversionError),
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::InvalidUrl(e) => write!(f, "invalid url: {}", e),
+            ClientError::Transport(e) => write!(f, "error connecting: {}", e),
+            ClientError::Status(status) => write!(
+                f,
+                "request failed: {:?}: {}",
+                status.code(),
+                status.message()
+            ),
+            ClientError::InvalidResponse(e) => write!(f, "invalid response: {}", e),
+            ClientError::InvalidTransaction(e) => write!(f, "invalid transaction: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<transport::Error> for ClientError {
+    fn from(e: transport::Error) -> Self {
+        ClientError::Transport(e)
+    }
+}
+
+impl From<Status> for ClientError {
+    fn from(status: Status) -> Self {
+        ClientError::Status(status)
+    }
+}
+
+/// The next slot led by a validator connected to the block engine.
+#[derive(Clone, Debug)]
+pub struct NextScheduledLeader {
+    pub current_slot: Slot,
+    pub next_leader_slot: Slot,
+    pub next_leader_identity: Pubkey,
+}
+
+/// Aborts the access token renewal once the last client clone is dropped.
+struct RenewalTask(JoinHandle<()>);
+
+impl Drop for RenewalTask {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+type InterceptedChannel = InterceptedService<Channel, ClientInterceptor>;
+
+/// A block engine searcher client. Clones share the connection and access token.
+///
+/// The channel reconnects on its own after the connection drops; requests that fail meanwhile are
+/// retried with backoff up to [MAX_RETRIES] times. Queries are retried on any `UNAVAILABLE`
+/// status. Bundles are only resent after transport errors, since the block engine also returns
+/// `UNAVAILABLE` for bundles it rejected, e.g. while shutting down.
+#[derive(Clone)]
+pub struct SearcherClient {
+    searcher: SearcherServiceClient<InterceptedChannel>,
+    searcher_ext: SearcherExtServiceClient<InterceptedChannel>,
+    _renewal_task: Option<Arc<RenewalTask>>,
+}
+
+impl SearcherClient {
+    /// Connects without authenticating, for block engines that don't require auth.
+    pub async fn connect(searcher_url: &str) -> Result<Self, ClientError> {
+        let channel = connect_channel(searcher_url).await?;
+        Ok(Self::new(channel, ClientInterceptor::default(), None))
+    }
+
+    /// Authenticates the keypair as a searcher with the auth service at `auth_url`, then
+    /// connects. The access token is refreshed in the background until the client is dropped.
+    pub async fn connect_authenticated(
+        searcher_url: &str,
+        auth_url: &str,
+        keypair: Arc<Keypair>,
+    ) -> Result<Self, ClientError> {
+        let auth_channel = connect_channel(auth_url).await?;
+        let authenticator = Authenticator::new(auth_channel, keypair, Role::Searcher).await?;
+        let interceptor = authenticator.interceptor();
+        let channel = connect_channel(searcher_url).await?;
+        let renewal_task = RenewalTask(tokio::spawn(authenticator.run()));
+        Ok(Self::new(
+            channel,
+            interceptor,
+            Some(Arc::new(renewal_task)),
+        ))
+    }
+
+    fn new(
+        channel: Channel,
+        interceptor: ClientInterceptor,
+        renewal_task: Option<Arc<RenewalTask>>,
+    ) -> Self {
+        SearcherClient {
+            searcher: SearcherServiceClient::with_interceptor(channel.clone(), interceptor.clone()),
+            searcher_ext: SearcherExtServiceClient::with_interceptor(channel, interceptor),
+            _renewal_task: renewal_task,
+        }
+    }
+
+    /// Sends the transactions as a bundle and returns its uuid.
+    pub async fn send_bundle(
+        &self,
+        transactions: &[VersionedTransaction],
+    ) -> Result<String, ClientError> {
+        let bundle =
+            bundle_from_versioned_txs(transactions).map_err(ClientError::InvalidTransaction)?;
+        let request = SendBundleRequest {
+            bundle: Some(bundle),
+        };
+        let response = with_retries(is_transport_error, || {
+            let mut searcher = self.searcher.clone();
+            let request = request.clone();
+            async move { searcher.send_bundle(request).await }
+        })
+        .await?;
+        Ok(response.uuid)
+    }
+
+    pub async fn next_scheduled_leader(&self) -> Result<NextScheduledLeader, ClientError> {
+        let response = with_retries(is_unavailable, || {
+            let mut searcher = self.searcher.clone();
+            async move {
+                searcher
+                    .get_next_scheduled_leader(NextScheduledLeaderRequest {})
+                    .await
+            }
+        })
+        .await?;
+        Ok(NextScheduledLeader {
+            current_slot: response.current_slot,
+            next_leader_slot: response.next_leader_slot,
+            next_leader_identity: parse_pubkey(&response.next_leader_identity)?,
+        })
+    }
+
+    /// Validators connected to the block engine and their upcoming leader slots.
+    pub async fn connected_leaders(&self) -> Result<HashMap<Pubkey, Vec<Slot>>, ClientError> {
+        let response = with_retries(is_unavailable, || {
+            let mut searcher = self.searcher.clone();
+            async move {
+                searcher
+                    .get_connected_leaders(ConnectedLeadersRequest {})
+                    .await
+            }
+        })
+        .await?;
+        response
+            .connected_validators
+            .into_iter()
+            .map(|(identity, slots)| Ok((parse_pubkey(&identity)?, slots.slots)))
+            .collect()
+    }
+
+    pub async fn tip_accounts(&self) -> Result<Vec<Pubkey>, ClientError> {
+        let response = with_retries(is_unavailable, || {
+            let mut searcher = self.searcher.clone();
+            async move { searcher.get_tip_accounts(GetTipAccountsRequest {}).await }
+        })
+        .await?;
+        response
+            .accounts
+            .iter()
+            .map(|account| parse_pubkey(account))
+            .collect()
+    }
+
+    /// Streams transactions pending in the block engine's mempool that touch any of the accounts.
+    pub async fn subscribe_mempool(
+        &self,
+        accounts: Vec<String>,
+    ) -> Result<Streaming<PendingTxNotification>, ClientError> {
+        with_retries(is_unavailable, || {
+            let mut searcher = self.searcher.clone();
+            let accounts = accounts.clone();
+            async move {
+                searcher
+                    .subscribe_pending_transactions(PendingTxSubscriptionRequest { accounts })
+                    .await
+            }
+        })
+        .await
+    }
+
+    /// Streams the status of each bundle, then again whenever it changes. The stream isn't
+    /// resumed if the connection drops; subscribe again to pick up the current statuses.
+    pub async fn subscribe_bundle_statuses(
+        &self,
+        uuids: Vec<String>,
+    ) -> Result<Streaming<BundleStatus>, ClientError> {
+        with_retries(is_unavailable, || {
+            let mut searcher_ext = self.searcher_ext.clone();
+            let uuids = uuids.clone();
+            async move {
+                searcher_ext
+                    .subscribe_bundle_statuses(SubscribeBundleStatusesRequest { uuids })
+                    .await
+            }
+        })
+        .await
+    }
+}
+
+async fn connect_channel(url: &str) -> Result<Channel, ClientError> {
+    let endpoint = Channel::from_shared(url.to_string())
+        .map_err(|e| ClientError::InvalidUrl(format!("{}: {}", url, e)))?;
+    Ok(endpoint.connect().await?)
+}
+
+/// Makes the request, retrying failures `is_retryable` accepts.
+async fn with_retries<T, F, Fut>(
+    is_retryable: fn(&Status) -> bool,
+    mut request: F,
+) -> Result<T, ClientError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<Response<T>, Status>>,
+{
+    let mut retry_delay = INITIAL_RETRY_DELAY;
+    let mut retries = 0;
+    loop {
+        match request().await {
+            Ok(response) => return Ok(response.into_inner()),
+            Err(status) if is_retryable(&status) && retries < MAX_RETRIES => {
+                warn!(
+                    "request failed, retrying in {:?}: {:?}: {}",
+                    retry_delay,
+                    status.code(),
+                    status.message()
+                );
+                sleep(retry_delay).await;
+                retry_delay *= 2;
+                retries += 1;
+            }
+            Err(status) => return Err(status.into()),
+        }
+    }
+}
+
+/// Whether a query may be retried: the channel couldn't reach the block engine or it isn't
+/// serving yet. Queries don't change anything, so retrying is always safe.
+fn is_unavailable(status: &Status) -> bool {
+    status.code() == Code::Unavailable || is_transport_error(status)
+}
+
+/// Whether the request failed in the transport rather than being answered by the block engine.
+/// Statuses the block engine returns never have a source; tonic sets one on the `UNAVAILABLE` it
+/// returns when the server refused the stream before handling it.
+fn is_transport_error(status: &Status) -> bool {
+    match status.code() {
+        Code::Unknown => TRANSPORT_ERROR_MESSAGES
+            .iter()
+            .any(|message| status.message().starts_with(message)),
+        Code::Unavailable => status.source().is_some(),
+        _ => false,
+    }
+}
+
+fn parse_pubkey(value: &str) -> Result<Pubkey, ClientError> {
+    Pubkey::from_str(value)
+        .map_err(|_| ClientError::InvalidResponse(format!("invalid pubkey: {}", value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundles_not_retried_on_server_unavailable() {
+        for status in [
+            Status::unavailable("block engine shutting down"),
+            Status::unavailable("timed out fetching address lookup tables"),
+        ] {
+            assert!(!is_transport_error(&status));
+            assert!(is_unavailable(&status));
+        }
+    }
+
+    #[test]
+    fn test_transport_errors_retried() {
+        for status in [
+            Status::new(Code::Unknown, "transport error"),
+            Status::new(
+                Code::Unknown,
+                "Service was not ready: transport error: error trying to connect",
+            ),
+        ] {
+            assert!(is_transport_error(&status));
+            assert!(is_unavailable(&status));
+        }
+    }
+
+    #[test]
+    fn test_other_statuses_not_retried() {
+        for status in [
+            Status::unknown("bundle handler panicked"),
+            Status::invalid_argument("bundle is empty"),
+            Status::resource_exhausted("rate limited"),
+        ] {
+            assert!(!is_transport_error(&status));
+            assert!(!is_unavailable(&status));
+        }
+    }
+}
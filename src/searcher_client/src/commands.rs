@@ -0,0 +1,180 @@
+use crate::output::{emit, OutputFormat};
+use jito_protos::searcher_ext::{BundleState, BundleStatus};
+use jito_protos::versioned_tx_from_packet;
+use jito_searcher_client::client::SearcherClient;
+use serde_json::{json, Value};
+use solana_sdk::transaction::VersionedTransaction;
+use std::error::Error;
+use std::fs::read_to_string;
+use std::path::Path;
+
+/// Reads a file of base64-encoded, bincode-serialized transactions, one per line. Blank lines are
+/// ignored.
+fn read_transactions(path: &Path) -> Result<Vec<VersionedTransaction>, Box<dyn Error>> {
+    read_to_string(path)?
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            let bytes = base64::decode(line.trim())
+                .map_err(|e| format!("line {}: invalid base64: {}", index + 1, e))?;
+            let transaction = bincode::deserialize(&bytes)
+                .map_err(|e| format!("line {}: invalid transaction: {}", index + 1, e))?;
+            Ok(transaction)
+        })
+        .collect()
+}
+
+pub async fn send_bundle(
+    client: &SearcherClient,
+    tx_file: &Path,
+    output: OutputFormat,
+) -> Result<(), Box<dyn Error>> {
+    let transactions = read_transactions(tx_file)?;
+    let uuid = client.send_bundle(&transactions).await?;
+    emit(
+        output,
+        format!("bundle uuid: {}", uuid),
+        json!({ "uuid": uuid }),
+    );
+    Ok(())
+}
+
+pub async fn tip_accounts(
+    client: &SearcherClient,
+    output: OutputFormat,
+) -> Result<(), Box<dyn Error>> {
+    let accounts: Vec<String> = client
+        .tip_accounts()
+        .await?
+        .iter()
+        .map(|account| account.to_string())
+        .collect();
+    emit(output, accounts.join("\n"), json!(accounts));
+    Ok(())
+}
+
+pub async fn next_leader(
+    client: &SearcherClient,
+    output: OutputFormat,
+) -> Result<(), Box<dyn Error>> {
+    let leader = client.next_scheduled_leader().await?;
+    emit(
+        output,
+        format!(
+            "current slot: {}\nnext leader slot: {}\nnext leader: {}",
+            leader.current_slot, leader.next_leader_slot, leader.next_leader_identity
+        ),
+        json!({
+            "current_slot": leader.current_slot,
+            "next_leader_slot": leader.next_leader_slot,
+            "next_leader_identity": leader.next_leader_identity.to_string(),
+        }),
+    );
+    Ok(())
+}
+
+pub async fn connected_leaders(
+    client: &SearcherClient,
+    output: OutputFormat,
+) -> Result<(), Box<dyn Error>> {
+    let mut leaders: Vec<_> = client.connected_leaders().await?.into_iter().collect();
+    leaders.sort_unstable_by_key(|(identity, _)| identity.to_string());
+
+    let human = leaders
+        .iter()
+        .map(|(identity, slots)| {
+            let slots: Vec<String> = slots.iter().map(|slot| slot.to_string()).collect();
+            format!("{}: [{}]", identity, slots.join(", "))
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let json: serde_json::Map<String, Value> = leaders
+        .into_iter()
+        .map(|(identity, slots)| (identity.to_string(), json!(slots)))
+        .collect();
+    emit(output, human, Value::Object(json));
+    Ok(())
+}
+
+/// Prints pending transactions touching the accounts until the stream ends.
+pub async fn subscribe_mempool(
+    client: &SearcherClient,
+    accounts: Vec<String>,
+    output: OutputFormat,
+) -> Result<(), Box<dyn Error>> {
+    let mut notifications = client.subscribe_mempool(accounts).await?;
+    while let Some(notification) = notifications.message().await? {
+        let signatures: Vec<String> = notification
+            .transactions
+            .iter()
+            .map(|packet| match versioned_tx_from_packet(packet) {
+                Ok(transaction) => transaction
+                    .signatures
+                    .first()
+                    .map(|signature| signature.to_string())
+                    .unwrap_or_default(),
+                Err(_) => "<invalid transaction>".to_string(),
+            })
+            .collect();
+        let expires_at_ms = notification
+            .expiration_time
+            .map(|ts| ts.seconds * 1_000 + ts.nanos as i64 / 1_000_000);
+        emit(
+            output,
+            format!(
+                "{} pending transactions, expiring at {:?} ms: {}",
+                signatures.len(),
+                expires_at_ms,
+                signatures.join(", ")
+            ),
+            json!({
+                "expiration_time_ms": expires_at_ms,
+                "signatures": signatures,
+            }),
+        );
+    }
+    Ok(())
+}
+
+/// Prints the bundle's status, and with `follow` every change to it until interrupted.
+pub async fn bundle_status(
+    client: &SearcherClient,
+    uuid: String,
+    follow: bool,
+    output: OutputFormat,
+) -> Result<(), Box<dyn Error>> {
+    let mut statuses = client.subscribe_bundle_statuses(vec![uuid]).await?;
+    while let Some(status) = statuses.message().await? {
+        print_bundle_status(&status, output);
+        if !follow {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn print_bundle_status(status: &BundleStatus, output: OutputFormat) {
+    let state = BundleState::from_i32(status.state).unwrap_or(BundleState::Unknown);
+    let state = format!("{:?}", state).to_lowercase();
+    emit(
+        output,
+        format!(
+            "{}: {} (sent: {} delivered: {} dropped: {}) delivered to: [{}]",
+            status.uuid,
+            state,
+            status.validators_sent,
+            status.validators_delivered,
+            status.validators_dropped,
+            status.delivered_to.join(", ")
+        ),
+        json!({
+            "uuid": status.uuid,
+            "state": state,
+            "validators_sent": status.validators_sent,
+            "validators_delivered": status.validators_delivered,
+            "validators_dropped": status.validators_dropped,
+            "delivered_to": status.delivered_to,
+        }),
+    );
+}
@@ -0,0 +1,190 @@
+use crate::landing::MAX_SIGNATURES_PER_REQUEST;
+use log::{info, warn};
+use solana_client::client_error::ClientError;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signature, Signer};
+use solana_sdk::system_instruction;
+use solana_sdk::transaction::{Transaction, TransactionError};
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+
+/// Lamports each keypair is topped up to by default.
+pub const DEFAULT_FUNDING_LAMPORTS: u64 = 100_000_000_000;
+
+/// How long funding transactions have to reach the commitment level.
+const CONFIRM_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Wait before the first status poll; doubles after each one up to [MAX_POLL_DELAY].
+const INITIAL_POLL_DELAY: Duration = Duration::from_millis(250);
+const MAX_POLL_DELAY: Duration = Duration::from_secs(2);
+
+/// Transfers batched into one faucet transaction, keeping it under the packet size limit.
+const MAX_TRANSFERS_PER_TRANSACTION: usize = 16;
+
+#[derive(Debug)]
+pub enum FundingError {
+    Rpc(ClientError),
+    /// The funding transaction for the pubkeys landed but failed.
+    Failed(Vec<Pubkey>, TransactionError),
+    /// Funding transactions for the pubkeys didn't reach the commitment level in time.
+    Timeout(Vec<Pubkey>),
+}
+
+impl fmt::Display for FundingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FundingError::Rpc(e) => write!(f, "rpc error: {}", e),
+            FundingError::Failed(pubkeys, e) => write!(f, "funding {:?} failed: {}", pubkeys, e),
+            FundingError::Timeout(pubkeys) => write!(
+                f,
+                "funding not confirmed after {:?}: {:?}",
+                CONFIRM_TIMEOUT, pubkeys
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FundingError {}
+
+impl From<ClientError> for FundingError {
+    fn from(e: ClientError) -> Self {
+        FundingError::Rpc(e)
+    }
+}
+
+/// Where funds come from.
+pub enum FundingSource {
+    Airdrop,
+    /// Transfers from a funded keypair, for clusters where airdrops are disabled.
+    Faucet(Keypair),
+}
+
+/// Tops keypairs up to a balance before they sign transactions.
+pub struct Funder {
+    pub source: FundingSource,
+    pub lamports: u64,
+    /// Level the funding transactions, and the balances checked beforehand, must reach.
+    pub commitment: CommitmentConfig,
+}
+
+impl Funder {
+    /// Funds every pubkey whose balance is below `lamports` with the difference and waits for
+    /// the funding transactions to reach the commitment level.
+    pub async fn fund(
+        &self,
+        rpc_client: &RpcClient,
+        pubkeys: &[Pubkey],
+    ) -> Result<(), FundingError> {
+        let mut transfers = Vec::new();
+        for pubkey in pubkeys {
+            let balance = rpc_client
+                .get_balance_with_commitment(pubkey, self.commitment)
+                .await?
+                .value;
+            if balance < self.lamports {
+                transfers.push((*pubkey, self.lamports - balance));
+            }
+        }
+        if transfers.is_empty() {
+            return Ok(());
+        }
+
+        let signatures = match &self.source {
+            FundingSource::Airdrop => request_airdrops(rpc_client, &transfers).await?,
+            FundingSource::Faucet(faucet) => {
+                transfer_from_faucet(rpc_client, faucet, &transfers).await?
+            }
+        };
+        confirm_signatures(rpc_client, signatures, self.commitment).await?;
+        info!("funded {} pubkeys", transfers.len());
+        Ok(())
+    }
+}
+
+async fn request_airdrops(
+    rpc_client: &RpcClient,
+    transfers: &[(Pubkey, u64)],
+) -> Result<HashMap<Signature, Vec<Pubkey>>, FundingError> {
+    info!("requesting airdrops for {} pubkeys", transfers.len());
+    let mut signatures = HashMap::new();
+    for (pubkey, lamports) in transfers {
+        let signature = rpc_client.request_airdrop(pubkey, *lamports).await?;
+        signatures.insert(signature, vec![*pubkey]);
+    }
+    Ok(signatures)
+}
+
+async fn transfer_from_faucet(
+    rpc_client: &RpcClient,
+    faucet: &Keypair,
+    transfers: &[(Pubkey, u64)],
+) -> Result<HashMap<Signature, Vec<Pubkey>>, FundingError> {
+    info!(
+        "transferring from faucet {} to {} pubkeys",
+        faucet.pubkey(),
+        transfers.len()
+    );
+    let blockhash = rpc_client.get_latest_blockhash().await?;
+    let mut signatures = HashMap::new();
+    for chunk in transfers.chunks(MAX_TRANSFERS_PER_TRANSACTION) {
+        let instructions = system_instruction::transfer_many(&faucet.pubkey(), chunk);
+        let transaction = Transaction::new_signed_with_payer(
+            &instructions,
+            Some(&faucet.pubkey()),
+            &[faucet],
+            blockhash,
+        );
+        let signature = rpc_client.send_transaction(&transaction).await?;
+        signatures.insert(signature, chunk.iter().map(|(pubkey, _)| *pubkey).collect());
+    }
+    Ok(signatures)
+}
+
+/// Polls with backoff until every signature reaches the commitment level, failing on the first
+/// transaction error or after [CONFIRM_TIMEOUT].
+async fn confirm_signatures(
+    rpc_client: &RpcClient,
+    mut pending: HashMap<Signature, Vec<Pubkey>>,
+    commitment: CommitmentConfig,
+) -> Result<(), FundingError> {
+    let start = Instant::now();
+    let mut poll_delay = INITIAL_POLL_DELAY;
+    while !pending.is_empty() {
+        if start.elapsed() > CONFIRM_TIMEOUT {
+            return Err(FundingError::Timeout(
+                pending.into_values().flatten().collect(),
+            ));
+        }
+        sleep(poll_delay).await;
+        poll_delay = (poll_delay * 2).min(MAX_POLL_DELAY);
+
+        let signatures: Vec<Signature> = pending.keys().copied().collect();
+        let mut statuses = Vec::with_capacity(signatures.len());
+        for chunk in signatures.chunks(MAX_SIGNATURES_PER_REQUEST) {
+            match rpc_client.get_signature_statuses(chunk).await {
+                Ok(response) => statuses.extend(response.value),
+                Err(e) => {
+                    warn!("error polling funding statuses: {}", e);
+                    break;
+                }
+            }
+        }
+        for (signature, status) in signatures.iter().zip(statuses) {
+            let status = match status {
+                Some(status) => status,
+                None => continue,
+            };
+            if let Some(e) = status.err {
+                return Err(FundingError::Failed(pending.remove(signature).unwrap(), e));
+            }
+            if status.satisfies_commitment(commitment) {
+                pending.remove(signature);
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,354 @@
+use crate::output::percentile;
+use log::warn;
+use serde_json::{json, Value};
+use solana_client::client_error::ClientError;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::VersionedTransaction;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::error::TryRecvError;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::task::JoinHandle;
+use tokio::time::sleep;
+
+/// How often the statuses of unresolved bundles are polled. Bounds the resolution of the
+/// time-to-land measurements.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Most signatures `getSignatureStatuses` accepts per request.
+pub const MAX_SIGNATURES_PER_REQUEST: usize = 256;
+
+/// A bundle accepted by the block engine, to be checked for landing.
+#[derive(Clone, Debug)]
+pub struct SentBundle {
+    pub uuid: String,
+    pub signatures: Vec<Signature>,
+    /// Block height after which the bundle's blockhash expires and it can no longer land.
+    pub last_valid_block_height: u64,
+    pub sent_at: Instant,
+}
+
+impl SentBundle {
+    pub fn new(
+        uuid: String,
+        transactions: &[VersionedTransaction],
+        last_valid_block_height: u64,
+        sent_at: Instant,
+    ) -> Self {
+        SentBundle {
+            uuid,
+            signatures: transactions
+                .iter()
+                .filter_map(|transaction| transaction.signatures.first().copied())
+                .collect(),
+            last_valid_block_height,
+            sent_at,
+        }
+    }
+}
+
+/// How a sent bundle resolved.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Landing {
+    /// Every transaction landed and succeeded.
+    Landed,
+    /// Every transaction landed, but at least one failed.
+    Failed,
+    /// The blockhash expired without any transaction landing.
+    Expired,
+    /// The blockhash expired with only some of the transactions landed, breaking the bundle's
+    /// all-or-nothing guarantee.
+    Partial,
+}
+
+/// How tracked bundles resolved.
+#[derive(Clone, Debug, Default)]
+pub struct LandingStats {
+    pub tracked: u64,
+    pub landed: u64,
+    pub failed: u64,
+    pub expired: u64,
+    pub partial: u64,
+    /// Time from sending to seeing the bundle land, for landed and failed bundles.
+    pub times_to_land: Vec<Duration>,
+}
+
+impl LandingStats {
+    fn record(&mut self, bundle: &SentBundle, landing: Landing) {
+        match landing {
+            Landing::Landed => self.landed += 1,
+            Landing::Failed => self.failed += 1,
+            Landing::Expired => self.expired += 1,
+            Landing::Partial => self.partial += 1,
+        }
+        if matches!(landing, Landing::Landed | Landing::Failed) {
+            self.times_to_land.push(bundle.sent_at.elapsed());
+        }
+    }
+
+    pub fn pending(&self) -> u64 {
+        self.tracked - self.landed - self.failed - self.expired - self.partial
+    }
+
+    /// Human and JSON summaries for including in a command's report.
+    pub fn summary(&self) -> (String, Value) {
+        let mut times_to_land = self.times_to_land.clone();
+        times_to_land.sort_unstable();
+        let times_ms: Vec<f64> = [50.0, 90.0, 99.0, 100.0]
+            .iter()
+            .map(|p| percentile(&times_to_land, *p).as_secs_f64() * 1_000.0)
+            .collect();
+
+        let mut human = format!(
+            "bundles tracked: {} landed: {} failed: {} expired: {} pending: {}",
+            self.tracked,
+            self.landed,
+            self.failed,
+            self.expired,
+            self.pending()
+        );
+        if self.partial > 0 {
+            let _ = write!(
+                human,
+                "\natomicity violations (partially landed bundles): {}",
+                self.partial
+            );
+        }
+        let _ = write!(
+            human,
+            "\ntime to land ms: p50 {:.0} p90 {:.0} p99 {:.0} max {:.0}",
+            times_ms[0], times_ms[1], times_ms[2], times_ms[3]
+        );
+        let json = json!({
+            "tracked": self.tracked,
+            "landed": self.landed,
+            "failed": self.failed,
+            "expired": self.expired,
+            "partial": self.partial,
+            "pending": self.pending(),
+            "time_to_land_ms": {
+                "p50": times_ms[0],
+                "p90": times_ms[1],
+                "p99": times_ms[2],
+                "max": times_ms[3],
+            },
+        });
+        (human, json)
+    }
+}
+
+/// Polls `getSignatureStatuses` in the background until each tracked bundle has landed or its
+/// blockhash has expired.
+pub struct LandingTracker {
+    sender: UnboundedSender<SentBundle>,
+    stats: Arc<Mutex<LandingStats>>,
+    task: JoinHandle<()>,
+}
+
+impl LandingTracker {
+    /// Transactions count as landed once their status reaches `commitment`.
+    pub fn start(rpc_client: Arc<RpcClient>, commitment: CommitmentConfig) -> Self {
+        let (sender, receiver) = unbounded_channel();
+        let stats = Arc::new(Mutex::new(LandingStats::default()));
+        let task = tokio::spawn(track_landing(
+            rpc_client,
+            commitment,
+            receiver,
+            stats.clone(),
+        ));
+        LandingTracker {
+            sender,
+            stats,
+            task,
+        }
+    }
+
+    /// A sender for tracking bundles from other tasks. The tracker keeps running until it and
+    /// every sender are dropped.
+    pub fn sender(&self) -> UnboundedSender<SentBundle> {
+        self.sender.clone()
+    }
+
+    pub fn track(&self, bundle: SentBundle) {
+        let _ = self.sender.send(bundle);
+    }
+
+    pub fn stats(&self) -> LandingStats {
+        self.stats.lock().unwrap().clone()
+    }
+
+    /// Waits for every tracked bundle to resolve.
+    pub async fn finish(self) -> LandingStats {
+        drop(self.sender);
+        if let Err(e) = self.task.await {
+            warn!("landing tracker failed: {}", e);
+        }
+        self.stats.lock().unwrap().clone()
+    }
+}
+
+async fn track_landing(
+    rpc_client: Arc<RpcClient>,
+    commitment: CommitmentConfig,
+    mut receiver: UnboundedReceiver<SentBundle>,
+    stats: Arc<Mutex<LandingStats>>,
+) {
+    let mut pending = Vec::new();
+    let mut closed = false;
+    loop {
+        while !closed {
+            match receiver.try_recv() {
+                Ok(bundle) => {
+                    stats.lock().unwrap().tracked += 1;
+                    pending.push(bundle);
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => closed = true,
+            }
+        }
+        if closed && pending.is_empty() {
+            return;
+        }
+        if !pending.is_empty() {
+            let result = poll_statuses(&rpc_client, commitment, &mut pending, |bundle, landing| {
+                stats.lock().unwrap().record(bundle, landing)
+            })
+            .await;
+            if let Err(e) = result {
+                warn!("error polling signature statuses: {}", e);
+            }
+        }
+        sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// Polls until every bundle has landed or expired.
+pub async fn wait_for_landing(
+    rpc_client: &RpcClient,
+    commitment: CommitmentConfig,
+    mut bundles: Vec<SentBundle>,
+) -> Vec<(SentBundle, Landing)> {
+    let mut resolved = Vec::with_capacity(bundles.len());
+    while !bundles.is_empty() {
+        let result = poll_statuses(rpc_client, commitment, &mut bundles, |bundle, landing| {
+            resolved.push((bundle.clone(), landing))
+        })
+        .await;
+        if let Err(e) = result {
+            warn!("error polling signature statuses: {}", e);
+        }
+        if !bundles.is_empty() {
+            sleep(POLL_INTERVAL).await;
+        }
+    }
+    resolved
+}
+
+/// Removes resolved bundles from `pending`, calling `on_resolved` for each.
+async fn poll_statuses(
+    rpc_client: &RpcClient,
+    commitment: CommitmentConfig,
+    pending: &mut Vec<SentBundle>,
+    mut on_resolved: impl FnMut(&SentBundle, Landing),
+) -> Result<(), ClientError> {
+    // fetched before the statuses so a bundle isn't counted as expired after landing in between
+    let block_height = rpc_client
+        .get_block_height_with_commitment(commitment)
+        .await?;
+    let signatures: Vec<Signature> = pending
+        .iter()
+        .flat_map(|bundle| bundle.signatures.iter().copied())
+        .collect();
+    let mut statuses = Vec::with_capacity(signatures.len());
+    for chunk in signatures.chunks(MAX_SIGNATURES_PER_REQUEST) {
+        statuses.extend(rpc_client.get_signature_statuses(chunk).await?.value);
+    }
+
+    let mut statuses = statuses.into_iter();
+    pending.retain(|bundle| {
+        let mut landed = 0;
+        let mut failed = false;
+        for status in statuses.by_ref().take(bundle.signatures.len()).flatten() {
+            if status.satisfies_commitment(commitment) {
+                landed += 1;
+                failed |= status.err.is_some();
+            }
+        }
+
+        let landing = match classify(bundle, landed, failed, block_height) {
+            Some(landing) => landing,
+            None => return true,
+        };
+        if landing == Landing::Partial {
+            warn!(
+                "bundle {} partially landed: {} of {} transactions",
+                bundle.uuid,
+                landed,
+                bundle.signatures.len()
+            );
+        }
+        on_resolved(bundle, landing);
+        false
+    });
+    Ok(())
+}
+
+/// How a bundle resolved with `landed` of its transactions landed at `block_height`, or None if
+/// it may still land.
+fn classify(
+    bundle: &SentBundle,
+    landed: usize,
+    failed: bool,
+    block_height: u64,
+) -> Option<Landing> {
+    if landed == bundle.signatures.len() {
+        Some(if failed {
+            Landing::Failed
+        } else {
+            Landing::Landed
+        })
+    } else if block_height <= bundle.last_valid_block_height {
+        None
+    } else if landed == 0 {
+        Some(Landing::Expired)
+    } else {
+        Some(Landing::Partial)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bundle(num_signatures: usize, last_valid_block_height: u64) -> SentBundle {
+        SentBundle {
+            uuid: "uuid".to_string(),
+            signatures: (0..num_signatures)
+                .map(|_| Signature::new_unique())
+                .collect(),
+            last_valid_block_height,
+            sent_at: Instant::now(),
+        }
+    }
+
+    #[test]
+    fn test_classify_landed() {
+        let bundle = bundle(3, 100);
+        assert_eq!(classify(&bundle, 3, false, 50), Some(Landing::Landed));
+        assert_eq!(classify(&bundle, 3, true, 50), Some(Landing::Failed));
+        // landing counts even if seen after the blockhash expired
+        assert_eq!(classify(&bundle, 3, false, 101), Some(Landing::Landed));
+    }
+
+    #[test]
+    fn test_classify_pending_until_expired() {
+        let bundle = bundle(3, 100);
+        assert_eq!(classify(&bundle, 0, false, 50), None);
+        assert_eq!(classify(&bundle, 2, false, 100), None);
+        assert_eq!(classify(&bundle, 0, false, 101), Some(Landing::Expired));
+        assert_eq!(classify(&bundle, 2, true, 101), Some(Landing::Partial));
+    }
+}
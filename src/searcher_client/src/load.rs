@@ -0,0 +1,328 @@
+use crate::funding::Funder;
+use crate::landing::{LandingStats, LandingTracker, SentBundle};
+use crate::lookup_table::create_lookup_table;
+use crate::output::{emit, percentile, OutputFormat};
+use crate::transfers::TransferBuilder;
+use jito_searcher_client::client::{ClientError, SearcherClient};
+use log::info;
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::seq::SliceRandom;
+use rand::thread_rng;
+use serde_json::json;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::hash::Hash;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::Write;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::unbounded_channel;
+use tokio::time::sleep_until;
+
+/// How often the load generator fetches a new blockhash.
+const BLOCKHASH_REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Bundle sizes to pick from with relative weights, parsed from `<size>[:<weight>],...`, e.g.
+/// `1:2,5:1` sends single-transaction bundles twice as often as five-transaction ones.
+#[derive(Clone, Debug)]
+pub struct BundleSizes {
+    sizes: Vec<usize>,
+    weights: WeightedIndex<u32>,
+}
+
+impl BundleSizes {
+    fn sample(&self) -> usize {
+        self.sizes[self.weights.sample(&mut thread_rng())]
+    }
+}
+
+impl FromStr for BundleSizes {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut sizes = Vec::new();
+        let mut weights = Vec::new();
+        for entry in s.split(',') {
+            let (size, weight) = match entry.split_once(':') {
+                Some((size, weight)) => (size, weight),
+                None => (entry, "1"),
+            };
+            let size: usize = size
+                .trim()
+                .parse()
+                .map_err(|e| format!("invalid bundle size {:?}: {}", size, e))?;
+            if size == 0 {
+                return Err("bundle sizes must be at least 1".to_string());
+            }
+            let weight: u32 = weight
+                .trim()
+                .parse()
+                .map_err(|e| format!("invalid weight {:?}: {}", weight, e))?;
+            sizes.push(size);
+            weights.push(weight);
+        }
+        let weights = WeightedIndex::new(weights)
+            .map_err(|e| format!("invalid bundle size weights: {}", e))?;
+        Ok(BundleSizes { sizes, weights })
+    }
+}
+
+/// A transfer to a tip account added to the last transaction of every bundle.
+#[derive(Clone, Debug)]
+pub struct Tip {
+    /// Picked from the block engine's tip accounts if unset.
+    pub account: Option<Pubkey>,
+    pub lamports: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct LoadConfig {
+    /// Bundles per second across all connections.
+    pub rate: f64,
+    pub bundle_sizes: BundleSizes,
+    pub duration: Duration,
+    pub tip: Option<Tip>,
+    /// Check which accepted bundles land and include it in the report.
+    pub verify_landing: bool,
+}
+
+#[derive(Default)]
+struct LoadStats {
+    sent: u64,
+    accepted: u64,
+    /// Rejected requests by error.
+    errors: BTreeMap<String, u64>,
+    latencies: Vec<Duration>,
+}
+
+impl LoadStats {
+    fn record(&mut self, result: Result<String, ClientError>, latency: Duration) {
+        self.sent += 1;
+        self.latencies.push(latency);
+        match result {
+            Ok(_) => self.accepted += 1,
+            Err(e) => *self.errors.entry(error_kind(&e)).or_default() += 1,
+        }
+    }
+}
+
+fn error_kind(e: &ClientError) -> String {
+    match e {
+        ClientError::Status(status) => format!("{:?}", status.code()),
+        ClientError::Transport(_) | ClientError::InvalidUrl(_) => "transport".to_string(),
+        ClientError::InvalidResponse(_) => "invalid_response".to_string(),
+        ClientError::InvalidTransaction(_) => "invalid_transaction".to_string(),
+    }
+}
+
+/// Funds the signers and sends bundles of transfers at `config.rate` for `config.duration`,
+/// spreading them across the clients, then prints throughput, errors and `send_bundle` latency
+/// percentiles. Each request runs on its own task so slow responses don't lower the send rate.
+///
+/// With `config.verify_landing`, the report waits until every accepted bundle has landed or
+/// expired.
+pub async fn run_load(
+    clients: Vec<SearcherClient>,
+    rpc_client: Arc<RpcClient>,
+    funder: &Funder,
+    mut transfers: TransferBuilder,
+    config: LoadConfig,
+    output: OutputFormat,
+) -> Result<(), Box<dyn Error>> {
+    let tip = match &config.tip {
+        Some(Tip {
+            account: Some(account),
+            lamports,
+        }) => Some((vec![*account], *lamports)),
+        Some(Tip {
+            account: None,
+            lamports,
+        }) => {
+            let accounts = clients[0].tip_accounts().await?;
+            if accounts.is_empty() {
+                return Err("block engine has no tip accounts; pass --tip-account".into());
+            }
+            Some((accounts, *lamports))
+        }
+        None => None,
+    };
+
+    funder.fund(&rpc_client, &transfers.pubkeys()).await?;
+    if transfers.wants_lookup_table() {
+        let mut addresses = transfers.lookup_table_addresses();
+        if let Some((accounts, _)) = &tip {
+            addresses.extend(accounts);
+        }
+        let lookup_table = create_lookup_table(
+            &rpc_client,
+            transfers.lookup_table_authority(),
+            addresses,
+            funder.commitment,
+        )
+        .await?;
+        transfers.set_lookup_table(lookup_table);
+    }
+
+    info!(
+        "sending {} bundles/s for {:?} over {} connections",
+        config.rate,
+        config.duration,
+        clients.len()
+    );
+    let tracker = config
+        .verify_landing
+        .then(|| LandingTracker::start(rpc_client.clone(), CommitmentConfig::confirmed()));
+    let (result_sender, mut result_receiver) = unbounded_channel();
+    let period = Duration::from_secs_f64(1.0 / config.rate);
+    let start = Instant::now();
+    let (mut blockhash, mut last_valid_block_height) = latest_blockhash(&rpc_client).await?;
+    let mut blockhash_fetched_at = Instant::now();
+    for index in 0u32.. {
+        let send_at = start + period * index;
+        if send_at >= start + config.duration {
+            break;
+        }
+        sleep_until(send_at.into()).await;
+
+        if blockhash_fetched_at.elapsed() > BLOCKHASH_REFRESH_INTERVAL {
+            (blockhash, last_valid_block_height) = latest_blockhash(&rpc_client).await?;
+            blockhash_fetched_at = Instant::now();
+        }
+        let tip = tip
+            .as_ref()
+            .map(|(accounts, lamports)| (accounts.choose(&mut thread_rng()).unwrap(), *lamports));
+        let transactions = transfers.build(config.bundle_sizes.sample(), tip, blockhash);
+
+        let client = clients[index as usize % clients.len()].clone();
+        let result_sender = result_sender.clone();
+        let landing_sender = tracker.as_ref().map(LandingTracker::sender);
+        tokio::spawn(async move {
+            let sent_at = Instant::now();
+            let result = client.send_bundle(&transactions).await;
+            if let (Ok(uuid), Some(landing_sender)) = (&result, landing_sender) {
+                let _ = landing_sender.send(SentBundle::new(
+                    uuid.clone(),
+                    &transactions,
+                    last_valid_block_height,
+                    sent_at,
+                ));
+            }
+            let _ = result_sender.send((result, sent_at.elapsed()));
+        });
+    }
+    drop(result_sender);
+
+    let mut stats = LoadStats::default();
+    while let Some((result, latency)) = result_receiver.recv().await {
+        stats.record(result, latency);
+    }
+    stats.latencies.sort_unstable();
+    let elapsed = start.elapsed();
+
+    let landing = match tracker {
+        Some(tracker) => {
+            info!("waiting for sent bundles to land or expire");
+            Some(tracker.finish().await)
+        }
+        None => None,
+    };
+    report(&stats, landing.as_ref(), &config, elapsed, output);
+    Ok(())
+}
+
+/// The latest blockhash and the last block height it's valid for.
+async fn latest_blockhash(rpc_client: &RpcClient) -> Result<(Hash, u64), Box<dyn Error>> {
+    Ok(rpc_client
+        .get_latest_blockhash_with_commitment(CommitmentConfig::processed())
+        .await?)
+}
+
+fn report(
+    stats: &LoadStats,
+    landing: Option<&LandingStats>,
+    config: &LoadConfig,
+    elapsed: Duration,
+    output: OutputFormat,
+) {
+    let throughput = stats.accepted as f64 / elapsed.as_secs_f64();
+    let percentiles = [50.0, 90.0, 99.0, 100.0];
+    let latencies_ms: Vec<f64> = percentiles
+        .iter()
+        .map(|p| percentile(&stats.latencies, *p).as_secs_f64() * 1_000.0)
+        .collect();
+
+    let mut human = format!(
+        "sent {} bundles in {:.1}s (target {} bundles/s)\naccepted: {} ({:.1} bundles/s)\nrejected: {}",
+        stats.sent,
+        elapsed.as_secs_f64(),
+        config.rate,
+        stats.accepted,
+        throughput,
+        stats.sent - stats.accepted
+    );
+    for (error, count) in &stats.errors {
+        let _ = write!(human, "\n  {}: {}", error, count);
+    }
+    let _ = write!(
+        human,
+        "\nsend_bundle latency ms: p50 {:.2} p90 {:.2} p99 {:.2} max {:.2}",
+        latencies_ms[0], latencies_ms[1], latencies_ms[2], latencies_ms[3]
+    );
+    let landing = landing.map(|landing| {
+        let (landing_human, landing_json) = landing.summary();
+        let _ = write!(human, "\n{}", landing_human);
+        landing_json
+    });
+
+    emit(
+        output,
+        human,
+        json!({
+            "elapsed_secs": elapsed.as_secs_f64(),
+            "target_rate": config.rate,
+            "sent": stats.sent,
+            "accepted": stats.accepted,
+            "rejected": stats.sent - stats.accepted,
+            "throughput": throughput,
+            "errors": stats.errors,
+            "latency_ms": {
+                "p50": latencies_ms[0],
+                "p90": latencies_ms[1],
+                "p99": latencies_ms[2],
+                "max": latencies_ms[3],
+            },
+            "landing": landing,
+        }),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_bundle_sizes() {
+        let bundle_sizes: BundleSizes = "1:2, 5:1".parse().unwrap();
+        assert_eq!(bundle_sizes.sizes, vec![1, 5]);
+        assert!((1..1_000).all(|_| [1, 5].contains(&bundle_sizes.sample())));
+
+        // the weight defaults to 1
+        let bundle_sizes: BundleSizes = "3".parse().unwrap();
+        assert_eq!(bundle_sizes.sizes, vec![3]);
+        assert_eq!(bundle_sizes.sample(), 3);
+
+        // a zero weight is never picked
+        let bundle_sizes: BundleSizes = "2:0,4:1".parse().unwrap();
+        assert!((1..1_000).all(|_| bundle_sizes.sample() == 4));
+    }
+
+    #[test]
+    fn test_parse_bundle_sizes_rejects_invalid() {
+        for s in ["", "0", "-1", "two", "1:x", "1:-1", "1:0", "1:0,2:0"] {
+            assert!(s.parse::<BundleSizes>().is_err(), "{}", s);
+        }
+    }
+}
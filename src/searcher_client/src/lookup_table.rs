@@ -0,0 +1,98 @@
+use log::info;
+use solana_address_lookup_table_program::instruction;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::address_lookup_table_account::AddressLookupTableAccount;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::transaction::Transaction;
+use std::collections::HashSet;
+use std::error::Error;
+use std::time::Duration;
+use tokio::time::sleep;
+
+/// Most addresses a lookup table holds.
+const MAX_ADDRESSES: usize = 256;
+
+/// Addresses added per extend transaction, keeping it under the packet size limit.
+const MAX_ADDRESSES_PER_EXTEND: usize = 20;
+
+const SLOT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Creates a lookup table holding the addresses, with `authority` as its authority and payer,
+/// and waits until the addresses can be looked up.
+pub async fn create_lookup_table(
+    rpc_client: &RpcClient,
+    authority: &Keypair,
+    addresses: Vec<Pubkey>,
+    commitment: CommitmentConfig,
+) -> Result<AddressLookupTableAccount, Box<dyn Error>> {
+    let mut seen = HashSet::new();
+    let addresses: Vec<Pubkey> = addresses
+        .into_iter()
+        .filter(|address| seen.insert(*address))
+        .collect();
+    if addresses.len() > MAX_ADDRESSES {
+        return Err(format!(
+            "lookup tables hold at most {} addresses, got {}",
+            MAX_ADDRESSES,
+            addresses.len()
+        )
+        .into());
+    }
+
+    // the table address derives from a slot that must still be in the slot hashes sysvar
+    let recent_slot = rpc_client
+        .get_slot_with_commitment(CommitmentConfig::finalized())
+        .await?;
+    let (create, key) =
+        instruction::create_lookup_table(authority.pubkey(), authority.pubkey(), recent_slot);
+    send_and_confirm(rpc_client, authority, &[create], commitment).await?;
+    for chunk in addresses.chunks(MAX_ADDRESSES_PER_EXTEND) {
+        let extend = instruction::extend_lookup_table(
+            key,
+            authority.pubkey(),
+            Some(authority.pubkey()),
+            chunk.to_vec(),
+        );
+        send_and_confirm(rpc_client, authority, &[extend], commitment).await?;
+    }
+
+    // addresses can only be looked up from the slot after they were added
+    let extended_slot = rpc_client.get_slot_with_commitment(commitment).await?;
+    while rpc_client
+        .get_slot_with_commitment(CommitmentConfig::processed())
+        .await?
+        <= extended_slot
+    {
+        sleep(SLOT_POLL_INTERVAL).await;
+    }
+
+    info!(
+        "created lookup table {} with {} addresses",
+        key,
+        addresses.len()
+    );
+    Ok(AddressLookupTableAccount { key, addresses })
+}
+
+async fn send_and_confirm(
+    rpc_client: &RpcClient,
+    payer: &Keypair,
+    instructions: &[Instruction],
+    commitment: CommitmentConfig,
+) -> Result<(), Box<dyn Error>> {
+    let blockhash = rpc_client.get_latest_blockhash().await?;
+    let transaction = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&payer.pubkey()),
+        &[payer],
+        blockhash,
+    );
+    let signature = rpc_client.send_transaction(&transaction).await?;
+    rpc_client
+        .poll_for_signature_with_commitment(&signature, commitment)
+        .await?;
+    Ok(())
+}
@@ -1,31 +1,35 @@
-use clap::Parser;
-
-use std::{
-    process::exit,
-    sync::Arc,
-    time::{Duration, Instant, SystemTime},
-};
-
-use jito_protos::searcher::searcher_service_client::SearcherServiceClient;
-use jito_protos::{
-    bundle::Bundle, proto_packet_from_versioned_tx, searcher::SendBundleRequest, shared::Header,
-};
-use log::{error, info};
+mod blast;
+mod commands;
+mod funding;
+mod landing;
+mod load;
+mod lookup_table;
+mod output;
+mod scenario;
+mod transfers;
+
+use crate::funding::{Funder, FundingSource, DEFAULT_FUNDING_LAMPORTS};
+use crate::load::{BundleSizes, LoadConfig, Tip};
+use crate::output::OutputFormat;
+use crate::transfers::{SignerArgs, TransferBuilder};
+use clap::{Parser, Subcommand};
+use jito_searcher_client::client::SearcherClient;
+use log::error;
 use solana_client::nonblocking::rpc_client::RpcClient;
-use solana_sdk::{
-    commitment_config::{CommitmentConfig, CommitmentLevel},
-    pubkey::Pubkey,
-    signature::{read_keypair_file, Signer},
-    system_transaction,
-    transaction::VersionedTransaction,
-};
+use solana_sdk::commitment_config::{CommitmentConfig, CommitmentLevel};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{read_keypair_file, Keypair};
+use std::error::Error;
+use std::path::PathBuf;
+use std::process::exit;
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::runtime::Builder;
-use tokio::time::sleep;
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 struct Args {
-    /// RPC url to request airdrop from
+    /// RPC url to fund keypairs and check transactions through
     #[clap(short, long, env, default_value_t = String::from("http://localhost:8899"))]
     rpc_url: String,
 
@@ -33,37 +37,259 @@ struct Args {
     #[clap(short, long, env, default_value_t = String::from("grpc://localhost:1234"))]
     searcher_service_url: String,
 
-    /// Path to the keypair used to sign auth tokens.
-    /// Ensure the associated pubkey is added to the auth store before running this script.
+    /// URL for auth service
+    #[clap(long, env, default_value_t = String::from("grpc://localhost:1005"))]
+    auth_service_url: String,
+
+    /// Path to the keypair used to sign auth challenges and, unless other signers are given,
+    /// generated transactions.
+    /// Ensure the associated pubkey is allowed by the block engine's auth allowlist.
     #[clap(short, long, env, default_value_t = String::from("./keypair.json"))]
     keypair_path: String,
+
+    /// Connect without authenticating
+    #[clap(long, env)]
+    no_auth: bool,
+
+    /// Fund keypairs by transfer from this keypair instead of by airdrop, for clusters where
+    /// airdrops are disabled
+    #[clap(long, env)]
+    faucet_keypair_path: Option<String>,
+
+    /// Balance keypairs are topped up to before sending transactions
+    #[clap(long, env, default_value_t = DEFAULT_FUNDING_LAMPORTS)]
+    funding_lamports: u64,
+
+    /// Commitment level funding must reach before sending transactions
+    #[clap(long, env, default_value = "confirmed")]
+    funding_commitment: CommitmentLevel,
+
+    /// Output format
+    #[clap(long, env, arg_enum, default_value = "human")]
+    output: OutputFormat,
+
+    #[clap(subcommand)]
+    command: Command,
 }
 
-async fn request_and_confirm_airdrop(client: &RpcClient, pubkeys: &[Pubkey]) -> bool {
-    let mut sigs = Vec::new();
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Send a bundle of transactions read from a file
+    SendBundle {
+        /// File of base64-encoded, bincode-serialized transactions, one per line
+        #[clap(long)]
+        tx_file: PathBuf,
+    },
+    /// Print the tip accounts
+    TipAccounts,
+    /// Print the next slot led by a validator connected to the block engine
+    NextLeader,
+    /// Print the connected validators and their upcoming leader slots
+    ConnectedLeaders,
+    /// Stream pending transactions that touch any of the accounts
+    SubscribeMempool {
+        /// Comma-separated accounts
+        #[clap(long, value_delimiter = ',', required = true)]
+        accounts: Vec<String>,
+    },
+    /// Print a bundle's delivery status
+    BundleStatus {
+        uuid: String,
 
-    info!("requesting airdrop pubkeys: {:?}", pubkeys);
+        /// Keep printing the status whenever it changes
+        #[clap(long)]
+        follow: bool,
+    },
+    /// Fund the signers, then send transfer bundles every millisecond until interrupted
+    Blast {
+        #[clap(flatten)]
+        signers: SignerArgs,
 
-    for pubkey in pubkeys {
-        let signature = client
-            .request_airdrop(pubkey, 100000000000)
-            .await
-            .expect("gets signature");
-        sigs.push(signature);
-    }
+        /// Check which bundles land and periodically print a summary
+        #[clap(long)]
+        verify_landing: bool,
+    },
+    /// Fund the signers, then send bundles at a fixed rate and report throughput, errors
+    /// and send_bundle latency
+    Load {
+        /// Bundles per second across all connections
+        #[clap(long, default_value_t = 100.0)]
+        rate: f64,
+
+        /// Bundle sizes with optional relative weights, e.g. "1:2,5:1"
+        #[clap(long, default_value = "5")]
+        bundle_sizes: BundleSizes,
+
+        #[clap(flatten)]
+        signers: SignerArgs,
+
+        /// Number of connections to spread bundles across
+        #[clap(long, default_value_t = 1)]
+        connections: usize,
+
+        /// How long to send bundles for, in seconds
+        #[clap(long, default_value_t = 60)]
+        duration_secs: u64,
+
+        /// Tip account to transfer to in the last transaction of every bundle; picked from the
+        /// block engine's tip accounts if --tip-lamports is set without it
+        #[clap(long)]
+        tip_account: Option<Pubkey>,
+
+        /// Lamports to tip per bundle
+        #[clap(long)]
+        tip_lamports: Option<u64>,
+
+        /// Wait for sent bundles to land or expire and include landing in the report
+        #[clap(long)]
+        verify_landing: bool,
+    },
+    /// Fund the keypair, then run a scenario file and exit non-zero if any bundle's
+    /// outcome differs from the expected one
+    Scenario {
+        /// Scenario file, JSON if it ends in .json and YAML otherwise
+        path: PathBuf,
+    },
+}
+
+async fn run(args: Args) -> Result<(), Box<dyn Error>> {
+    // blast, load and scenario sign their transfers with the keypair even when not authenticating
+    let needs_keypair = !args.no_auth
+        || matches!(
+            args.command,
+            Command::Blast { .. } | Command::Load { .. } | Command::Scenario { .. }
+        );
+    let keypair = if needs_keypair {
+        let keypair = read_keypair_file(&args.keypair_path)
+            .map_err(|e| format!("error reading keypair {}: {}", args.keypair_path, e))?;
+        Some(Arc::new(keypair))
+    } else {
+        None
+    };
+    // connections authenticate with the keypair unless --no-auth is set
+    let auth_keypair = if args.no_auth { None } else { keypair.clone() };
+    let client = connect(
+        &args.searcher_service_url,
+        &args.auth_service_url,
+        &auth_keypair,
+    )
+    .await?;
 
-    let now = Instant::now();
-    while now.elapsed() < Duration::from_secs(20) {
-        let r = client
-            .get_signature_statuses(&sigs)
+    let source = match &args.faucet_keypair_path {
+        Some(path) => FundingSource::Faucet(
+            read_keypair_file(path)
+                .map_err(|e| format!("error reading faucet keypair {}: {}", path, e))?,
+        ),
+        None => FundingSource::Airdrop,
+    };
+    let funder = Funder {
+        source,
+        lamports: args.funding_lamports,
+        commitment: CommitmentConfig {
+            commitment: args.funding_commitment,
+        },
+    };
+
+    let output = args.output;
+    match args.command {
+        Command::SendBundle { tx_file } => commands::send_bundle(&client, &tx_file, output).await,
+        Command::TipAccounts => commands::tip_accounts(&client, output).await,
+        Command::NextLeader => commands::next_leader(&client, output).await,
+        Command::ConnectedLeaders => commands::connected_leaders(&client, output).await,
+        Command::SubscribeMempool { accounts } => {
+            commands::subscribe_mempool(&client, accounts, output).await
+        }
+        Command::BundleStatus { uuid, follow } => {
+            commands::bundle_status(&client, uuid, follow, output).await
+        }
+        Command::Blast {
+            signers,
+            verify_landing,
+        } => {
+            let transfers = TransferBuilder::from_args(&signers, keypair.unwrap())?;
+            let rpc_client = Arc::new(RpcClient::new(args.rpc_url));
+            blast::blast(
+                &client,
+                rpc_client,
+                &funder,
+                transfers,
+                verify_landing,
+                output,
+            )
+            .await
+        }
+        Command::Load {
+            rate,
+            bundle_sizes,
+            signers,
+            connections,
+            duration_secs,
+            tip_account,
+            tip_lamports,
+            verify_landing,
+        } => {
+            if !rate.is_finite() || rate <= 0.0 || connections == 0 {
+                return Err("--rate and --connections must be positive".into());
+            }
+            let tip = match (tip_account, tip_lamports) {
+                (_, Some(lamports)) => Some(Tip {
+                    account: tip_account,
+                    lamports,
+                }),
+                (Some(_), None) => return Err("--tip-account requires --tip-lamports".into()),
+                (None, None) => None,
+            };
+            let mut clients = vec![client];
+            for _ in 1..connections {
+                clients.push(
+                    connect(
+                        &args.searcher_service_url,
+                        &args.auth_service_url,
+                        &auth_keypair,
+                    )
+                    .await?,
+                );
+            }
+            let config = LoadConfig {
+                rate,
+                bundle_sizes,
+                duration: Duration::from_secs(duration_secs),
+                tip,
+                verify_landing,
+            };
+            let transfers = TransferBuilder::from_args(&signers, keypair.unwrap())?;
+            let rpc_client = Arc::new(RpcClient::new(args.rpc_url));
+            load::run_load(clients, rpc_client, &funder, transfers, config, output).await
+        }
+        Command::Scenario { path } => {
+            let scenario = scenario::read_scenario(&path)
+                .map_err(|e| format!("error reading scenario {}: {}", path.display(), e))?;
+            let rpc_client = RpcClient::new(args.rpc_url);
+            scenario::run_scenario(
+                &client,
+                &rpc_client,
+                &funder,
+                &keypair.unwrap(),
+                &scenario,
+                output,
+            )
             .await
-            .expect("got statuses");
-        if r.value.iter().all(|s| s.is_some()) {
-            info!("got airdrop pubkeys: {:?}", pubkeys);
-            return true;
         }
     }
-    false
+}
+
+async fn connect(
+    searcher_url: &str,
+    auth_url: &str,
+    auth_keypair: &Option<Arc<Keypair>>,
+) -> Result<SearcherClient, Box<dyn Error>> {
+    let client = match auth_keypair {
+        Some(keypair) => {
+            SearcherClient::connect_authenticated(searcher_url, auth_url, keypair.clone()).await?
+        }
+        None => SearcherClient::connect(searcher_url).await?,
+    };
+    Ok(client)
 }
 
 fn main() {
@@ -71,66 +297,9 @@ fn main() {
 
     let args: Args = Args::parse();
 
-    let kp = Arc::new(read_keypair_file(args.keypair_path).expect("failed to read keypair file"));
-    let rpc_client = RpcClient::new(args.rpc_url);
-
     let runtime = Builder::new_multi_thread().enable_all().build().unwrap();
-    runtime.block_on(async move {
-        let mut searcher_client = SearcherServiceClient::connect(args.searcher_service_url)
-            .await
-            .expect("connect to searcher service");
-        if !request_and_confirm_airdrop(&rpc_client, &[kp.pubkey()]).await {
-            error!("error requesting airdrop");
-            exit(1);
-        }
-        sleep(Duration::from_secs(5)).await;
-
-        let mut last_blockhash_time = Instant::now();
-        let mut blockhash = rpc_client
-            .get_latest_blockhash_with_commitment(CommitmentConfig {
-                commitment: CommitmentLevel::Processed,
-            })
-            .await
-            .expect("latest blockhash")
-            .0;
-        let mut base = 0;
-
-        info!("sending bundles...");
-        loop {
-            if last_blockhash_time.elapsed() > Duration::from_secs(5) {
-                blockhash = rpc_client
-                    .get_latest_blockhash_with_commitment(CommitmentConfig {
-                        commitment: CommitmentLevel::Processed,
-                    })
-                    .await
-                    .expect("latest blockhash")
-                    .0;
-                last_blockhash_time = Instant::now();
-            }
-            let txs: Vec<_> = (0..5)
-                .map(|amount| {
-                    VersionedTransaction::from(system_transaction::transfer(
-                        &kp,
-                        &kp.pubkey(),
-                        base + amount,
-                        blockhash,
-                    ))
-                })
-                .collect();
-            base += txs.len() as u64;
-
-            let result = searcher_client
-                .send_bundle(SendBundleRequest {
-                    bundle: Some(Bundle {
-                        header: Some(Header {
-                            ts: Some(prost_types::Timestamp::from(SystemTime::now())),
-                        }),
-                        packets: txs.iter().map(proto_packet_from_versioned_tx).collect(),
-                    }),
-                })
-                .await;
-            info!("uuid: {:?}", result.unwrap().into_inner().uuid);
-            sleep(Duration::from_millis(1)).await;
-        }
-    });
+    if let Err(e) = runtime.block_on(run(args)) {
+        error!("{}", e);
+        exit(1);
+    }
 }
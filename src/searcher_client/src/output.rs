@@ -0,0 +1,46 @@
+use serde_json::Value;
+use std::time::Duration;
+
+/// How command results are printed.
+#[derive(clap::ArgEnum, Clone, Copy, Debug)]
+pub enum OutputFormat {
+    Human,
+    /// One JSON value per line
+    Json,
+}
+
+/// Prints one result in the chosen format.
+pub fn emit(output: OutputFormat, human: String, json: Value) {
+    match output {
+        OutputFormat::Human => println!("{}", human),
+        OutputFormat::Json => println!("{}", json),
+    }
+}
+
+/// The duration below which `percentile` percent of the sorted durations fall.
+pub fn percentile(sorted: &[Duration], percentile: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let index = ((sorted.len() - 1) as f64 * percentile / 100.0).round() as usize;
+    sorted[index]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentile() {
+        assert_eq!(percentile(&[], 50.0), Duration::ZERO);
+
+        let sorted: Vec<Duration> = (1..=100).map(Duration::from_millis).collect();
+        assert_eq!(percentile(&sorted, 0.0), Duration::from_millis(1));
+        assert_eq!(percentile(&sorted, 50.0), Duration::from_millis(51));
+        assert_eq!(percentile(&sorted, 99.0), Duration::from_millis(99));
+        assert_eq!(percentile(&sorted, 100.0), Duration::from_millis(100));
+
+        let single = [Duration::from_millis(7)];
+        assert_eq!(percentile(&single, 90.0), Duration::from_millis(7));
+    }
+}
@@ -0,0 +1,430 @@
+use crate::funding::Funder;
+use crate::landing::{wait_for_landing, Landing, SentBundle};
+use crate::output::{emit, OutputFormat};
+use jito_searcher_client::client::{ClientError, SearcherClient};
+use log::info;
+use rand::seq::SliceRandom;
+use rand::thread_rng;
+use serde::Deserialize;
+use serde_json::json;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::system_transaction;
+use solana_sdk::transaction::VersionedTransaction;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fs::read_to_string;
+use std::path::Path;
+use std::time::Instant;
+
+/// A sequence of bundles to send and the outcome expected for each.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    pub name: String,
+    pub steps: Vec<Step>,
+}
+
+/// One bundle of self-transfers from the keypair. The bundle holds, in order, the first
+/// transaction of the `conflicts_with` step, `transactions` transfers, and a tip.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Step {
+    pub name: String,
+    #[serde(default = "default_count")]
+    pub transactions: usize,
+    /// Indices of transfers that fail by moving more lamports than the keypair holds.
+    #[serde(default)]
+    pub failing: Vec<usize>,
+    /// Adds a transfer to a random tip account.
+    #[serde(default)]
+    pub tip_lamports: Option<u64>,
+    /// Times the identical bundle is sent.
+    #[serde(default = "default_count")]
+    pub submissions: usize,
+    /// An earlier step whose first transaction this bundle shares, so at most one of the two
+    /// can land. The shared transaction lands with whichever bundle does, so neither step is
+    /// judged on it.
+    #[serde(default)]
+    pub conflicts_with: Option<String>,
+    pub expect: Expect,
+}
+
+fn default_count() -> usize {
+    1
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Expect {
+    /// `send_bundle` fails.
+    Rejected,
+    /// `send_bundle` succeeds; landing is only checked for atomicity.
+    Accepted,
+    /// Every transaction lands and succeeds.
+    Landed,
+    /// Accepted, but nothing lands before the blockhash expires.
+    NotLanded,
+}
+
+/// What happened to one submission.
+#[derive(Clone, Debug)]
+enum Outcome {
+    Rejected(String),
+    Resolved(Landing),
+}
+
+impl Outcome {
+    fn landed(&self) -> bool {
+        matches!(
+            self,
+            Outcome::Resolved(Landing::Landed | Landing::Failed | Landing::Partial)
+        )
+    }
+
+    fn matches(&self, expect: Expect) -> bool {
+        match self {
+            Outcome::Rejected(_) => expect == Expect::Rejected,
+            Outcome::Resolved(landing) => match expect {
+                Expect::Rejected => false,
+                Expect::Accepted => *landing != Landing::Partial,
+                Expect::Landed => *landing == Landing::Landed,
+                Expect::NotLanded => *landing == Landing::Expired,
+            },
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Outcome::Rejected(e) => format!("rejected ({})", e),
+            Outcome::Resolved(Landing::Landed) => "landed".to_string(),
+            Outcome::Resolved(Landing::Failed) => "landed with failing transactions".to_string(),
+            Outcome::Resolved(Landing::Expired) => "not landed".to_string(),
+            Outcome::Resolved(Landing::Partial) => "partially landed".to_string(),
+        }
+    }
+}
+
+/// Reads a scenario from a `.json` file, or from YAML otherwise.
+pub fn read_scenario(path: &Path) -> Result<Scenario, Box<dyn Error>> {
+    let contents = read_to_string(path)?;
+    let scenario: Scenario = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => serde_json::from_str(&contents)?,
+        _ => serde_yaml::from_str(&contents)?,
+    };
+    scenario.validate()?;
+    Ok(scenario)
+}
+
+impl Scenario {
+    fn validate(&self) -> Result<(), String> {
+        let shared_first = self.shared_first_transactions();
+        let mut names = HashSet::new();
+        for step in &self.steps {
+            if let Some(conflicts_with) = &step.conflicts_with {
+                if !names.contains(conflicts_with.as_str()) {
+                    return Err(format!(
+                        "step {}: conflicts_with must name an earlier step, got {}",
+                        step.name, conflicts_with
+                    ));
+                }
+            }
+            if !names.insert(step.name.as_str()) {
+                return Err(format!("duplicate step name {}", step.name));
+            }
+            let own_transactions = step.transactions + step.tip_lamports.map_or(0, |_| 1);
+            match &step.conflicts_with {
+                None if step.transactions == 0 => {
+                    return Err(format!(
+                        "step {}: needs at least one transaction",
+                        step.name
+                    ));
+                }
+                None if shared_first.contains(step.name.as_str()) && own_transactions < 2 => {
+                    return Err(format!(
+                        "step {}: needs a transaction besides the one conflicting steps share",
+                        step.name
+                    ));
+                }
+                Some(conflicts_with) if own_transactions == 0 => {
+                    return Err(format!(
+                        "step {}: needs a transaction besides the one it shares with {}",
+                        step.name, conflicts_with
+                    ));
+                }
+                _ => {}
+            }
+            if let Some(index) = step.failing.iter().find(|i| **i >= step.transactions) {
+                return Err(format!(
+                    "step {}: failing index {} out of range",
+                    step.name, index
+                ));
+            }
+            if step.submissions == 0 {
+                return Err(format!("step {}: submissions must be positive", step.name));
+            }
+        }
+        Ok(())
+    }
+
+    /// Names of the steps whose first transaction later steps share.
+    fn shared_first_transactions(&self) -> HashSet<&str> {
+        self.steps
+            .iter()
+            .filter_map(|step| step.conflicts_with.as_deref())
+            .collect()
+    }
+}
+
+/// Funds the keypair, sends every step's bundles in order, waits for the accepted ones to land or
+/// expire, and prints each step's outcome. Fails if any outcome differs from what the step
+/// expects, or if both bundles of a conflicting pair landed.
+pub async fn run_scenario(
+    client: &SearcherClient,
+    rpc_client: &RpcClient,
+    funder: &Funder,
+    keypair: &Keypair,
+    scenario: &Scenario,
+    output: OutputFormat,
+) -> Result<(), Box<dyn Error>> {
+    let tip_accounts = if scenario
+        .steps
+        .iter()
+        .any(|step| step.tip_lamports.is_some())
+    {
+        let accounts = client.tip_accounts().await?;
+        if accounts.is_empty() {
+            return Err("block engine has no tip accounts".into());
+        }
+        accounts
+    } else {
+        Vec::new()
+    };
+
+    funder.fund(rpc_client, &[keypair.pubkey()]).await?;
+
+    info!("running scenario {}", scenario.name);
+    let (blockhash, last_valid_block_height) = rpc_client
+        .get_latest_blockhash_with_commitment(CommitmentConfig::processed())
+        .await?;
+    let shared_first = scenario.shared_first_transactions();
+    let mut bundles: HashMap<&str, Vec<VersionedTransaction>> = HashMap::new();
+    let mut submissions: Vec<(&Step, Result<SentBundle, ClientError>)> = Vec::new();
+    // unique transfer amounts keep every transaction's signature unique
+    let mut amount = 0;
+    for step in &scenario.steps {
+        let mut transactions = Vec::new();
+        if let Some(conflicts_with) = &step.conflicts_with {
+            transactions.extend(bundles[conflicts_with.as_str()].first().cloned());
+        }
+        for index in 0..step.transactions {
+            amount += 1;
+            let lamports = if step.failing.contains(&index) {
+                u64::MAX - amount
+            } else {
+                amount
+            };
+            transactions.push(VersionedTransaction::from(system_transaction::transfer(
+                keypair,
+                &keypair.pubkey(),
+                lamports,
+                blockhash,
+            )));
+        }
+        if let Some(lamports) = step.tip_lamports {
+            let tip_account = tip_accounts.choose(&mut thread_rng()).unwrap();
+            transactions.push(VersionedTransaction::from(system_transaction::transfer(
+                keypair,
+                tip_account,
+                lamports,
+                blockhash,
+            )));
+        }
+
+        // a shared first transaction lands with either bundle, so neither is judged on it
+        let judged = if step.conflicts_with.is_some() || shared_first.contains(step.name.as_str()) {
+            &transactions[1..]
+        } else {
+            &transactions[..]
+        };
+        for _ in 0..step.submissions {
+            let sent_at = Instant::now();
+            let result = client
+                .send_bundle(&transactions)
+                .await
+                .map(|uuid| SentBundle::new(uuid, judged, last_valid_block_height, sent_at));
+            submissions.push((step, result));
+        }
+        bundles.insert(&step.name, transactions);
+    }
+
+    info!("waiting for accepted bundles to land or expire");
+    let accepted = submissions
+        .iter()
+        .filter_map(|(_, result)| result.as_ref().ok().cloned())
+        .collect();
+    let landings: HashMap<String, Landing> =
+        wait_for_landing(rpc_client, CommitmentConfig::confirmed(), accepted)
+            .await
+            .into_iter()
+            .map(|(bundle, landing)| (bundle.uuid, landing))
+            .collect();
+
+    let mut outcomes: HashMap<&str, Vec<Outcome>> = HashMap::new();
+    for (step, result) in &submissions {
+        let outcome = match result {
+            Ok(bundle) => Outcome::Resolved(landings[&bundle.uuid]),
+            Err(ClientError::Status(status)) => Outcome::Rejected(format!("{:?}", status.code())),
+            Err(e) => Outcome::Rejected(e.to_string()),
+        };
+        outcomes.entry(&step.name).or_default().push(outcome);
+    }
+
+    let mut diverged = 0;
+    for step in &scenario.steps {
+        let step_outcomes = &outcomes[step.name.as_str()];
+        let mut passed = step_outcomes
+            .iter()
+            .all(|outcome| outcome.matches(step.expect));
+        let mut note = String::new();
+        if let Some(conflicts_with) = &step.conflicts_with {
+            let landed = |name: &str| outcomes[name].iter().any(Outcome::landed);
+            if landed(step.name.as_str()) && landed(conflicts_with.as_str()) {
+                passed = false;
+                note = format!(
+                    " (both this and conflicting step {} landed)",
+                    conflicts_with
+                );
+            }
+        }
+        if !passed {
+            diverged += 1;
+        }
+
+        let described: Vec<String> = step_outcomes.iter().map(Outcome::describe).collect();
+        emit(
+            output,
+            format!(
+                "{} {}: expected {:?}, got {}{}",
+                if passed { "PASS" } else { "FAIL" },
+                step.name,
+                step.expect,
+                described.join(", "),
+                note
+            ),
+            json!({
+                "step": step.name,
+                "passed": passed,
+                "expected": format!("{:?}", step.expect),
+                "outcomes": described,
+            }),
+        );
+    }
+
+    if diverged > 0 {
+        return Err(format!(
+            "scenario {}: {} of {} steps diverged from the expected outcome",
+            scenario.name,
+            diverged,
+            scenario.steps.len()
+        )
+        .into());
+    }
+    info!("scenario {} passed", scenario.name);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(yaml: &str) -> Result<(), String> {
+        serde_yaml::from_str::<Scenario>(yaml)
+            .map_err(|e| e.to_string())?
+            .validate()
+    }
+
+    /// Validates a scenario with the given steps, each in YAML flow style.
+    fn validate_steps(steps: &[&str]) -> Result<(), String> {
+        let steps: Vec<String> = steps.iter().map(|step| format!("  - {}", step)).collect();
+        parse(&format!("name: test\nsteps:\n{}", steps.join("\n")))
+    }
+
+    #[test]
+    fn test_shipped_scenario_is_valid() {
+        assert_eq!(
+            parse(include_str!("../scenarios/bundle_handling.yaml")),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_validate_rejects_invalid_steps() {
+        let invalid: &[&[&str]] = &[
+            // conflicts with a later step
+            &[
+                "{name: a, conflicts_with: b, expect: accepted}",
+                "{name: b, expect: accepted}",
+            ],
+            &["{name: a, expect: accepted}", "{name: a, expect: accepted}"],
+            &["{name: a, transactions: 0, expect: accepted}"],
+            // nothing left to judge either step on once the shared transaction is left out
+            &[
+                "{name: a, expect: accepted}",
+                "{name: b, conflicts_with: a, expect: accepted}",
+            ],
+            &[
+                "{name: a, transactions: 2, expect: accepted}",
+                "{name: b, transactions: 0, conflicts_with: a, expect: accepted}",
+            ],
+            &["{name: a, transactions: 2, failing: [2], expect: accepted}"],
+            &["{name: a, submissions: 0, expect: accepted}"],
+        ];
+        for steps in invalid {
+            assert!(validate_steps(steps).is_err(), "{:?}", steps);
+        }
+    }
+
+    #[test]
+    fn test_validate_counts_tips_as_own_transactions() {
+        assert_eq!(
+            validate_steps(&[
+                "{name: a, tip_lamports: 1, expect: accepted}",
+                "{name: b, transactions: 0, tip_lamports: 1, conflicts_with: a, expect: accepted}",
+            ]),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_outcome_matches() {
+        let rejected = Outcome::Rejected("InvalidArgument".to_string());
+        assert!(rejected.matches(Expect::Rejected));
+        assert!(!rejected.matches(Expect::Accepted));
+
+        let expects = [
+            Expect::Rejected,
+            Expect::Accepted,
+            Expect::Landed,
+            Expect::NotLanded,
+        ];
+        let cases = [
+            (Landing::Landed, [false, true, true, false]),
+            (Landing::Failed, [false, true, false, false]),
+            (Landing::Expired, [false, true, false, true]),
+            (Landing::Partial, [false, false, false, false]),
+        ];
+        for (landing, matches) in cases {
+            for (expect, expected) in expects.iter().zip(matches) {
+                assert_eq!(
+                    Outcome::Resolved(landing).matches(*expect),
+                    expected,
+                    "{:?} {:?}",
+                    landing,
+                    expect
+                );
+            }
+        }
+    }
+}
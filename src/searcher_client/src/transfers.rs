@@ -0,0 +1,180 @@
+use rand::{thread_rng, Rng};
+use solana_sdk::address_lookup_table_account::AddressLookupTableAccount;
+use solana_sdk::hash::Hash;
+use solana_sdk::message::{v0, VersionedMessage};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{read_keypair_file, Keypair, Signer};
+use solana_sdk::system_instruction;
+use solana_sdk::transaction::{Transaction, VersionedTransaction};
+use std::error::Error;
+use std::sync::Arc;
+
+/// Keypairs that sign generated bundles.
+#[derive(clap::Args, Debug)]
+pub struct SignerArgs {
+    /// Sign bundles with this many generated keypairs, funded before sending, instead of the
+    /// keypair
+    #[clap(long)]
+    signers: Option<usize>,
+
+    /// Sign bundles with the keypairs in these comma-separated files instead of the keypair
+    #[clap(long, value_delimiter = ',', conflicts_with = "signers")]
+    signer_keypair_paths: Vec<String>,
+
+    /// Fraction of bundles that transfer to an account shared by all signers rather than back to
+    /// their own, so they contend for its write lock
+    #[clap(long, default_value_t = 0.0)]
+    overlap: f64,
+
+    /// Send v0 transactions that look up the accounts they transfer to, other than the signer's
+    /// own, in an address lookup table created before sending
+    #[clap(long)]
+    lookup_table: bool,
+}
+
+/// Builds bundles of transfers, rotating through the signers so consecutive bundles lock
+/// different accounts and can execute in parallel.
+pub struct TransferBuilder {
+    signers: Vec<Arc<Keypair>>,
+    next_signer: usize,
+    overlap: f64,
+    shared_account: Pubkey,
+    blockhash: Hash,
+    /// Next transfer amount per signer. Transactions that only differ in amount get distinct
+    /// signatures; restarting at one for each new blockhash keeps the amounts small.
+    next_amounts: Vec<u64>,
+    wants_lookup_table: bool,
+    /// Builds v0 transactions against the table once set.
+    lookup_table: Option<AddressLookupTableAccount>,
+}
+
+impl TransferBuilder {
+    /// Signers come from the args, or are just `keypair` if the args don't name any.
+    pub fn from_args(args: &SignerArgs, keypair: Arc<Keypair>) -> Result<Self, Box<dyn Error>> {
+        if !(0.0..=1.0).contains(&args.overlap) {
+            return Err("--overlap must be between 0 and 1".into());
+        }
+        let signers = if let Some(count) = args.signers {
+            if count == 0 {
+                return Err("--signers must be positive".into());
+            }
+            (0..count).map(|_| Arc::new(Keypair::new())).collect()
+        } else if !args.signer_keypair_paths.is_empty() {
+            args.signer_keypair_paths
+                .iter()
+                .map(|path| {
+                    read_keypair_file(path)
+                        .map(Arc::new)
+                        .map_err(|e| format!("error reading signer keypair {}: {}", path, e))
+                })
+                .collect::<Result<_, _>>()?
+        } else {
+            vec![keypair]
+        };
+        Ok(Self::new(signers, args.overlap, args.lookup_table))
+    }
+
+    pub fn new(signers: Vec<Arc<Keypair>>, overlap: f64, wants_lookup_table: bool) -> Self {
+        let shared_account = signers[0].pubkey();
+        let next_amounts = vec![1; signers.len()];
+        TransferBuilder {
+            signers,
+            next_signer: 0,
+            overlap,
+            shared_account,
+            blockhash: Hash::default(),
+            next_amounts,
+            wants_lookup_table,
+            lookup_table: None,
+        }
+    }
+
+    /// True if [Self::set_lookup_table] should be called before building bundles.
+    pub fn wants_lookup_table(&self) -> bool {
+        self.wants_lookup_table
+    }
+
+    /// The signer that creates the lookup table.
+    pub fn lookup_table_authority(&self) -> &Keypair {
+        &self.signers[0]
+    }
+
+    /// Accounts the lookup table should hold, besides any tip accounts.
+    pub fn lookup_table_addresses(&self) -> Vec<Pubkey> {
+        let mut addresses = self.pubkeys();
+        addresses.push(self.shared_account);
+        addresses
+    }
+
+    pub fn set_lookup_table(&mut self, lookup_table: AddressLookupTableAccount) {
+        self.lookup_table = Some(lookup_table);
+    }
+
+    /// Pubkeys to fund before sending.
+    pub fn pubkeys(&self) -> Vec<Pubkey> {
+        self.signers.iter().map(|signer| signer.pubkey()).collect()
+    }
+
+    /// A bundle of `count` transfers from the next signer. With `tip`, the last transaction also
+    /// transfers the lamports to the tip account.
+    pub fn build(
+        &mut self,
+        count: usize,
+        tip: Option<(&Pubkey, u64)>,
+        blockhash: Hash,
+    ) -> Vec<VersionedTransaction> {
+        if blockhash != self.blockhash {
+            self.blockhash = blockhash;
+            self.next_amounts.iter_mut().for_each(|amount| *amount = 1);
+        }
+        let index = self.next_signer;
+        self.next_signer = (self.next_signer + 1) % self.signers.len();
+        let signer = &self.signers[index];
+        let to = if self.overlap > 0.0 && thread_rng().gen_bool(self.overlap) {
+            self.shared_account
+        } else {
+            signer.pubkey()
+        };
+
+        (0..count)
+            .map(|i| {
+                let amount = self.next_amounts[index];
+                self.next_amounts[index] += 1;
+                let mut instructions =
+                    vec![system_instruction::transfer(&signer.pubkey(), &to, amount)];
+                if i == count - 1 {
+                    if let Some((tip_account, lamports)) = tip {
+                        instructions.push(system_instruction::transfer(
+                            &signer.pubkey(),
+                            tip_account,
+                            lamports,
+                        ));
+                    }
+                }
+                match &self.lookup_table {
+                    Some(lookup_table) => {
+                        // only fails past 256 accounts; transfers use at most four
+                        let message = v0::Message::try_compile(
+                            &signer.pubkey(),
+                            &instructions,
+                            std::slice::from_ref(lookup_table),
+                            blockhash,
+                        )
+                        .expect("compiles");
+                        VersionedTransaction::try_new(
+                            VersionedMessage::V0(message),
+                            &[signer.as_ref()],
+                        )
+                        .expect("signs")
+                    }
+                    None => VersionedTransaction::from(Transaction::new_signed_with_payer(
+                        &instructions,
+                        Some(&signer.pubkey()),
+                        &[signer.as_ref()],
+                        blockhash,
+                    )),
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,100 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Decides what happens when a validator subscription's queue is full.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BackpressurePolicy {
+    /// Drop the item that didn't fit.
+    DropNewest,
+    /// Evict the oldest queued item to make room.
+    DropOldest,
+    /// Drop the item that didn't fit and close the subscription once this many items in a row
+    /// found the queue full.
+    Disconnect { max_consecutive_full: u32 },
+    /// Wait up to the timeout for the validator to make room, then drop the item. The
+    /// subscription falls behind the others while it waits and skips items if it lags too far.
+    Block { timeout: Duration },
+}
+
+impl fmt::Display for BackpressurePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BackpressurePolicy::DropNewest => write!(f, "drop_newest"),
+            BackpressurePolicy::DropOldest => write!(f, "drop_oldest"),
+            BackpressurePolicy::Disconnect {
+                max_consecutive_full,
+            } => write!(f, "disconnect:{}", max_consecutive_full),
+            BackpressurePolicy::Block { timeout } => write!(f, "block:{}", timeout.as_millis()),
+        }
+    }
+}
+
+impl FromStr for BackpressurePolicy {
+    type Err = String;
+
+    /// Parses `drop_newest`, `drop_oldest`, `disconnect:<max consecutive full>` or
+    /// `block:<timeout ms>`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, arg) = match s.split_once(':') {
+            Some((name, arg)) => (name, Some(arg)),
+            None => (s, None),
+        };
+        let parse_arg = || -> Result<u32, String> {
+            arg.ok_or_else(|| format!("{} requires an argument, e.g. {}:10", name, name))?
+                .parse()
+                .map_err(|e| format!("invalid {} argument: {}", name, e))
+        };
+        match (name, arg) {
+            ("drop_newest", None) => Ok(BackpressurePolicy::DropNewest),
+            ("drop_oldest", None) => Ok(BackpressurePolicy::DropOldest),
+            ("disconnect", _) => match parse_arg()? {
+                0 => Err("disconnect requires at least 1 consecutive full event".to_string()),
+                max_consecutive_full => Ok(BackpressurePolicy::Disconnect {
+                    max_consecutive_full,
+                }),
+            },
+            ("block", _) => Ok(BackpressurePolicy::Block {
+                timeout: Duration::from_millis(parse_arg()? as u64),
+            }),
+            _ => Err(format!("unknown backpressure policy: {}", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_round_trips() {
+        for policy in [
+            BackpressurePolicy::DropNewest,
+            BackpressurePolicy::DropOldest,
+            BackpressurePolicy::Disconnect {
+                max_consecutive_full: 10,
+            },
+            BackpressurePolicy::Block {
+                timeout: Duration::from_millis(250),
+            },
+        ] {
+            assert_eq!(policy.to_string().parse::<BackpressurePolicy>(), Ok(policy));
+        }
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid() {
+        for s in [
+            "",
+            "drop",
+            "drop_newest:1",
+            "disconnect",
+            "disconnect:0",
+            "disconnect:-1",
+            "block",
+            "block:soon",
+        ] {
+            assert!(s.parse::<BackpressurePolicy>().is_err(), "{}", s);
+        }
+    }
+}
@@ -0,0 +1,1660 @@
+use crate::backpressure::BackpressurePolicy;
+use crate::metrics::{
+    BACKPRESSURE_DISCONNECTS, BUNDLES_DROPPED, BUNDLES_FORWARDED, BUNDLE_BATCH_SIZE,
+    BUNDLE_DELIVERIES, BUNDLE_RETRIES, CHANNEL_FULL, CONNECTED_VALIDATORS, CONNECTION_EVENTS,
+    FORWARD_LATENCY, HEARTBEATS_SENT, PACKETS_DROPPED, PACKETS_FILTERED, PACKETS_FORWARDED,
+    SUBSCRIPTION_LAGGED,
+};
+use crate::packet_filter::PacketFilter;
+use crate::per_validator::PerValidator;
+use crate::routing::RoutingMode;
+use jito_leader_schedule::tracker::LeaderScheduleTracker;
+use jito_protos::block_engine::{SubscribeBundlesResponse, SubscribePacketsResponse};
+use jito_protos::bundle::BundleUuid;
+use jito_protos::header_now;
+use jito_protos::packet::PacketBatch;
+use log::{debug, info, warn};
+use solana_sdk::clock::{DEFAULT_MS_PER_SLOT, NUM_CONSECUTIVE_LEADER_SLOTS};
+use solana_sdk::pubkey::Pubkey;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::mpsc::{
+    channel, unbounded_channel, Permit, Receiver, Sender, UnboundedReceiver, UnboundedSender,
+};
+use tokio::sync::{broadcast, oneshot, watch};
+use tokio::task::{JoinError, JoinHandle};
+use tokio::time::{sleep_until, timeout, Instant as TokioInstant};
+use tokio_stream::Stream;
+use tonic::Status;
+use uuid::Uuid;
+
+/// Capacity of the broadcast channels feeding the subscription tasks. A subscription that falls
+/// further behind than this skips the oldest items.
+const BROADCAST_CAPACITY: usize = 1_000;
+
+/// Bundle batches whose delivery records are kept after dispatch, so the bundles a lagging
+/// subscription skipped can be recorded as dropped. Covers subscriptions that fall up to three
+/// broadcast channels' worth of batches behind.
+const DISPATCH_HISTORY_CAPACITY: usize = 4 * BROADCAST_CAPACITY;
+
+/// Items a subscription queues for its validator before its backpressure policy applies.
+const SUBSCRIPTION_QUEUE_CAPACITY: usize = 1_000;
+
+/// Capacity of each subscription's gRPC stream. Kept small so that items wait in the
+/// subscription queue, where the backpressure policy can still evict them.
+const STREAM_CHANNEL_CAPACITY: usize = 16;
+
+/// How long a closing subscription keeps sending its queue before giving up on the validator.
+const CLOSE_FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Most bundles sent to a validator in one response.
+const BUNDLE_BATCH_MAX_SIZE: usize = 32;
+
+/// Longest a bundle waits for others to fill its batch.
+const BUNDLE_BATCH_MAX_LATENCY: Duration = Duration::from_millis(1);
+
+/// How long a packet subscription's stream may go without a message before it's sent a heartbeat.
+/// Bundle streams don't get heartbeats, since an empty bundle batch would read as a real one.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Number of forwarded bundles kept for [Forwarder::recent_bundles].
+const RECENT_BUNDLES_CAPACITY: usize = 1_000;
+
+/// In leader-only mode, validators leading any slot from the current slot through this many
+/// slots ahead receive bundles.
+const LEADER_LOOKAHEAD_SLOTS: u64 = NUM_CONSECUTIVE_LEADER_SLOTS;
+
+/// How long after a bundle is dispatched a validator that failed to receive it gets it again when
+/// it resubscribes, roughly one leader's slot window.
+const BUNDLE_RETRY_WINDOW: Duration =
+    Duration::from_millis(DEFAULT_MS_PER_SLOT * NUM_CONSECUTIVE_LEADER_SLOTS);
+
+/// Most bundles held for retry per validator.
+const MAX_RETRIES_PER_VALIDATOR: usize = 1_000;
+
+/// Drop reasons after which a bundle is retried: the subscription went away rather than falling
+/// behind.
+const RETRYABLE_DROP_REASONS: &[&str] = &["channel_closed", "subscription_closed", "undelivered"];
+
+/// Called with true once a message is taken off a subscription's stream to be written to the
+/// validator, or false if the stream is dropped first.
+type DeliveryCallback = Box<dyn FnOnce(bool) + Send>;
+
+/// A message on a subscription's stream.
+struct Outgoing<T> {
+    message: Result<T, Status>,
+    on_delivery: Option<DeliveryCallback>,
+}
+
+impl<T> Outgoing<T> {
+    fn untracked(response: T) -> Self {
+        Outgoing {
+            message: Ok(response),
+            on_delivery: None,
+        }
+    }
+
+    fn status(status: Status) -> Self {
+        Outgoing {
+            message: Err(status),
+            on_delivery: None,
+        }
+    }
+}
+
+/// The validator on the other end of a subscription.
+#[derive(Clone, Copy)]
+struct Subscriber {
+    uuid: Uuid,
+    identity: Option<Pubkey>,
+}
+
+/// An item the dispatcher fans out to every subscription of one stream.
+trait Dispatch: Send + Sync + 'static {
+    type Response: Send + 'static;
+
+    /// Stream name used in logs and metric labels.
+    const STREAM: &'static str;
+
+    /// Builds the response for a subscriber, or None if the subscriber shouldn't receive it.
+    /// `filter` is the subscription's snapshot of its validator's packet filter.
+    fn response(&self, filter: &PacketFilter, identity: Option<&Pubkey>) -> Option<Self::Response>;
+
+    /// An empty response sent on an idle stream so the validator can tell the block engine is
+    /// still alive, or None if the stream has no such response.
+    fn heartbeat() -> Option<Self::Response> {
+        None
+    }
+
+    fn dispatched_at(&self) -> Instant;
+
+    /// Position of the item in its stream's dispatch order, if the stream keeps a history.
+    fn seq(&self) -> u64 {
+        0
+    }
+
+    /// Called when a lagging subscription skipped the `count` items dispatched just before the
+    /// one at `seq`.
+    fn on_skipped(_forwarder: &Forwarder, _subscriber: &Subscriber, _seq: u64, _count: u64) {}
+
+    fn on_sent(&self, response: &Self::Response);
+
+    fn on_dropped(&self, subscriber: &Subscriber, response: &Self::Response, reason: &'static str);
+
+    /// Called when the item is queued on a validator's new subscription after failing to reach
+    /// its previous one.
+    fn on_retried(&self, _subscriber: &Subscriber) {}
+
+    /// Tracks whether a sent response reaches the validator. None if delivery isn't tracked.
+    fn on_delivery(self: Arc<Self>, _subscriber: Subscriber) -> Option<DeliveryCallback> {
+        None
+    }
+}
+
+struct PacketDispatch {
+    batch: PacketBatch,
+    /// The current and upcoming leaders, if any subscription filters on them and the leader
+    /// schedule covers the lookahead. `leaders_only` filters pass everything when it doesn't.
+    leaders: Option<HashSet<Pubkey>>,
+    dispatched_at: Instant,
+}
+
+impl Dispatch for PacketDispatch {
+    type Response = SubscribePacketsResponse;
+
+    const STREAM: &'static str = "packets";
+
+    fn response(&self, filter: &PacketFilter, identity: Option<&Pubkey>) -> Option<Self::Response> {
+        if filter.leaders_only {
+            let is_leader = match (&self.leaders, identity) {
+                (Some(leaders), Some(identity)) => leaders.contains(identity),
+                (Some(_), None) => false,
+                (None, _) => true,
+            };
+            if !is_leader {
+                PACKETS_FILTERED
+                    .with_label_values(&["not_leader"])
+                    .inc_by(self.batch.packets.len() as u64);
+                return None;
+            }
+        }
+
+        let batch = filter.filter_batch(&self.batch);
+        let num_filtered = self.batch.packets.len() - batch.as_ref().map_or(0, |b| b.packets.len());
+        if num_filtered > 0 {
+            PACKETS_FILTERED
+                .with_label_values(&["filtered"])
+                .inc_by(num_filtered as u64);
+        }
+        Some(SubscribePacketsResponse {
+            header: Some(header_now()),
+            batch: Some(batch?),
+        })
+    }
+
+    fn heartbeat() -> Option<Self::Response> {
+        Some(SubscribePacketsResponse {
+            header: Some(header_now()),
+            batch: None,
+        })
+    }
+
+    fn dispatched_at(&self) -> Instant {
+        self.dispatched_at
+    }
+
+    fn on_sent(&self, response: &Self::Response) {
+        PACKETS_FORWARDED.inc_by(num_packets(response));
+    }
+
+    fn on_dropped(
+        &self,
+        _subscriber: &Subscriber,
+        response: &Self::Response,
+        reason: &'static str,
+    ) {
+        PACKETS_DROPPED
+            .with_label_values(&[reason])
+            .inc_by(num_packets(response));
+    }
+}
+
+fn num_packets(response: &SubscribePacketsResponse) -> u64 {
+    response
+        .batch
+        .as_ref()
+        .map_or(0, |batch| batch.packets.len() as u64)
+}
+
+/// Something that happened to a bundle on its way to one validator subscription.
+#[derive(Clone, Debug)]
+pub struct BundleEvent {
+    pub at: SystemTime,
+    pub subscription: Uuid,
+    pub identity: Option<Pubkey>,
+    pub kind: BundleEventKind,
+}
+
+#[derive(Clone, Debug)]
+pub enum BundleEventKind {
+    /// The bundle was taken off the subscription's stream to be written to the validator. This
+    /// isn't an acknowledgement: the write can still fail, and the bundle isn't retried if it does.
+    Delivered,
+    /// The bundle didn't reach the subscription's validator.
+    Dropped { reason: &'static str },
+    /// The bundle was queued again on the validator's new subscription.
+    Retried,
+}
+
+#[derive(Default)]
+struct ForwardStats {
+    sent: AtomicUsize,
+    delivered: AtomicUsize,
+    dropped: AtomicUsize,
+    events: Mutex<Vec<BundleEvent>>,
+}
+
+impl ForwardStats {
+    fn record(&self, subscriber: &Subscriber, kind: BundleEventKind) {
+        self.events.lock().unwrap().push(BundleEvent {
+            at: SystemTime::now(),
+            subscription: subscriber.uuid,
+            identity: subscriber.identity,
+            kind,
+        });
+    }
+}
+
+struct RetryEntry {
+    bundle: BundleUuid,
+    stats: Arc<ForwardStats>,
+    expires_at: Instant,
+}
+
+/// Bundles that didn't reach a validator. They're handed straight to the validator's current
+/// bundle subscription if it has one, or held until it resubscribes or the retry window ends.
+#[derive(Default)]
+struct RetryQueues {
+    held: HashMap<Pubkey, VecDeque<RetryEntry>>,
+    /// Each validator's current bundle subscription and the sender for its retries.
+    live: HashMap<Pubkey, (Uuid, UnboundedSender<Arc<BundleDispatch>>)>,
+}
+
+type DeliveryRetries = Arc<Mutex<RetryQueues>>;
+
+/// What's kept of a dispatched bundle batch once it may have left the broadcast channel.
+struct DispatchedBatch {
+    seq: u64,
+    recipients: Option<HashSet<Pubkey>>,
+    stats: Vec<Arc<ForwardStats>>,
+}
+
+#[derive(Default)]
+struct DispatchHistory {
+    next_seq: u64,
+    batches: VecDeque<DispatchedBatch>,
+}
+
+/// A batch of bundles sent to validators in a single response.
+struct BundleDispatch {
+    /// Position in the dispatch history. Zero for retries, which aren't broadcast.
+    seq: u64,
+    bundles: Vec<(BundleUuid, Arc<ForwardStats>)>,
+    /// Validators that should receive the bundles, or None for all of them.
+    recipients: Option<HashSet<Pubkey>>,
+    /// When the first bundle joined the batch.
+    dispatched_at: Instant,
+    /// Where bundles that don't reach a validator go, if retries are enabled.
+    retries: Option<DeliveryRetries>,
+    retry_until: Instant,
+}
+
+impl BundleDispatch {
+    fn drop_bundles(&self, subscriber: &Subscriber, reason: &'static str) {
+        BUNDLES_DROPPED
+            .with_label_values(&[reason])
+            .inc_by(self.bundles.len() as u64);
+        for (bundle, stats) in &self.bundles {
+            stats.dropped.fetch_add(1, Ordering::Relaxed);
+            stats.record(subscriber, BundleEventKind::Dropped { reason });
+            warn!(
+                "bundle dropped uuid: {} subscription: {:?} reason: {}",
+                bundle.uuid, subscriber.uuid, reason
+            );
+        }
+        if RETRYABLE_DROP_REASONS.contains(&reason) {
+            self.schedule_retry(subscriber);
+        }
+    }
+
+    /// Hands the bundles to the subscriber's validator's newer subscription, or holds them until
+    /// it has one, if the validator may still get them in time.
+    fn schedule_retry(&self, subscriber: &Subscriber) {
+        let (retries, identity) = match (&self.retries, subscriber.identity) {
+            (Some(retries), Some(identity)) => (retries, identity),
+            _ => return,
+        };
+        let now = Instant::now();
+        if now >= self.retry_until {
+            return;
+        }
+
+        let mut retries = retries.lock().unwrap();
+        if let Some((uuid, retry_sender)) = retries.live.get(&identity) {
+            // the subscription that dropped them may still be the validator's current one
+            if *uuid != subscriber.uuid {
+                let dispatch = Arc::new(BundleDispatch {
+                    seq: 0,
+                    bundles: self.bundles.clone(),
+                    recipients: None,
+                    dispatched_at: self.dispatched_at,
+                    retries: self.retries.clone(),
+                    retry_until: self.retry_until,
+                });
+                if retry_sender.send(dispatch).is_ok() {
+                    BUNDLE_RETRIES.inc_by(self.bundles.len() as u64);
+                    return;
+                }
+            }
+        }
+        let entries = retries.held.entry(identity).or_default();
+        entries.retain(|entry| entry.expires_at > now);
+        for (bundle, stats) in &self.bundles {
+            if entries.len() == MAX_RETRIES_PER_VALIDATOR {
+                entries.pop_front();
+            }
+            entries.push_back(RetryEntry {
+                bundle: bundle.clone(),
+                stats: stats.clone(),
+                expires_at: self.retry_until,
+            });
+        }
+    }
+}
+
+impl Dispatch for BundleDispatch {
+    type Response = SubscribeBundlesResponse;
+
+    const STREAM: &'static str = "bundles";
+
+    fn response(
+        &self,
+        _filter: &PacketFilter,
+        identity: Option<&Pubkey>,
+    ) -> Option<Self::Response> {
+        if let Some(recipients) = &self.recipients {
+            if !identity.map_or(false, |identity| recipients.contains(identity)) {
+                return None;
+            }
+        }
+        Some(SubscribeBundlesResponse {
+            bundles: self
+                .bundles
+                .iter()
+                .map(|(bundle, _)| bundle.clone())
+                .collect(),
+        })
+    }
+
+    fn dispatched_at(&self) -> Instant {
+        self.dispatched_at
+    }
+
+    fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    fn on_skipped(forwarder: &Forwarder, subscriber: &Subscriber, seq: u64, count: u64) {
+        let first = seq.saturating_sub(count);
+        let skipped: Vec<Arc<ForwardStats>> = forwarder
+            .bundle_history
+            .lock()
+            .unwrap()
+            .batches
+            .iter()
+            .filter(|batch| batch.seq >= first && batch.seq < seq)
+            .filter(|batch| {
+                batch.recipients.as_ref().map_or(true, |recipients| {
+                    subscriber
+                        .identity
+                        .map_or(false, |identity| recipients.contains(&identity))
+                })
+            })
+            .flat_map(|batch| batch.stats.iter().cloned())
+            .collect();
+        BUNDLES_DROPPED
+            .with_label_values(&["lagged"])
+            .inc_by(skipped.len() as u64);
+        for stats in &skipped {
+            stats.dropped.fetch_add(1, Ordering::Relaxed);
+            stats.record(subscriber, BundleEventKind::Dropped { reason: "lagged" });
+        }
+        warn!(
+            "bundles skipped by lagging subscription uuid: {:?} count: {}",
+            subscriber.uuid,
+            skipped.len()
+        );
+    }
+
+    fn on_sent(&self, _response: &Self::Response) {
+        BUNDLES_FORWARDED.inc_by(self.bundles.len() as u64);
+        for (_, stats) in &self.bundles {
+            stats.sent.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn on_dropped(
+        &self,
+        subscriber: &Subscriber,
+        _response: &Self::Response,
+        reason: &'static str,
+    ) {
+        self.drop_bundles(subscriber, reason);
+    }
+
+    fn on_retried(&self, subscriber: &Subscriber) {
+        for (_, stats) in &self.bundles {
+            stats.record(subscriber, BundleEventKind::Retried);
+        }
+    }
+
+    fn on_delivery(self: Arc<Self>, subscriber: Subscriber) -> Option<DeliveryCallback> {
+        Some(Box::new(move |delivered| {
+            if delivered {
+                BUNDLE_DELIVERIES
+                    .with_label_values(&["delivered"])
+                    .inc_by(self.bundles.len() as u64);
+                for (_, stats) in &self.bundles {
+                    stats.delivered.fetch_add(1, Ordering::Relaxed);
+                    stats.record(&subscriber, BundleEventKind::Delivered);
+                }
+            } else {
+                BUNDLE_DELIVERIES
+                    .with_label_values(&["undelivered"])
+                    .inc_by(self.bundles.len() as u64);
+                self.drop_bundles(&subscriber, "undelivered");
+            }
+        }))
+    }
+}
+
+/// Bundles waiting to be dispatched together.
+struct BundleBatch {
+    bundles: Vec<(BundleUuid, Arc<ForwardStats>)>,
+    started_at: Instant,
+}
+
+struct RecentBundle {
+    uuid: String,
+    forwarded_at: SystemTime,
+    num_packets: usize,
+    stats: Arc<ForwardStats>,
+}
+
+impl RecentBundle {
+    fn snapshot(&self) -> ForwardedBundle {
+        ForwardedBundle {
+            uuid: self.uuid.clone(),
+            forwarded_at: self.forwarded_at,
+            num_packets: self.num_packets,
+            validators_sent: self.stats.sent.load(Ordering::Relaxed),
+            validators_delivered: self.stats.delivered.load(Ordering::Relaxed),
+            validators_dropped: self.stats.dropped.load(Ordering::Relaxed),
+            events: self.stats.events.lock().unwrap().clone(),
+        }
+    }
+}
+
+/// The outcome of forwarding a bundle to the connected validators.
+#[derive(Clone)]
+pub struct ForwardedBundle {
+    pub uuid: String,
+    pub forwarded_at: SystemTime,
+    pub num_packets: usize,
+    /// Subscriptions the bundle was handed to, including retries.
+    pub validators_sent: usize,
+    /// Subscriptions whose validator the bundle reached.
+    pub validators_delivered: usize,
+    /// Subscriptions the bundle didn't reach, whether or not it was sent.
+    pub validators_dropped: usize,
+    /// Per-subscription delivery record.
+    pub events: Vec<BundleEvent>,
+}
+
+struct Subscription {
+    stream: &'static str,
+    identity: Option<Pubkey>,
+    connected_at: Instant,
+    /// Closes the subscription with a status and the reason recorded in its connection event.
+    disconnect_sender: Option<oneshot::Sender<(Status, &'static str)>>,
+}
+
+/// A validator subscription as reported to operators.
+pub struct SubscriptionInfo {
+    pub uuid: Uuid,
+    pub stream: &'static str,
+    pub identity: Option<Pubkey>,
+    pub age: Duration,
+    pub backpressure_policy: BackpressurePolicy,
+}
+
+/// The gRPC stream of a validator subscription. Dropping it, which tonic does as soon as the
+/// validator goes away, removes the subscription and stops its task.
+pub struct SubscriptionStream<T> {
+    inner: Receiver<Outgoing<T>>,
+    _guard: SubscriptionGuard,
+}
+
+impl<T> Stream for SubscriptionStream<T> {
+    type Item = Result<T, Status>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.inner.poll_recv(cx) {
+            Poll::Ready(Some(outgoing)) => {
+                if let Some(on_delivery) = outgoing.on_delivery {
+                    on_delivery(true);
+                }
+                Poll::Ready(Some(outgoing.message))
+            }
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<T> Drop for SubscriptionStream<T> {
+    fn drop(&mut self) {
+        // whatever the validator hasn't taken off the stream never reaches it
+        self.inner.close();
+        while let Ok(outgoing) = self.inner.try_recv() {
+            if let Some(on_delivery) = outgoing.on_delivery {
+                on_delivery(false);
+            }
+        }
+    }
+}
+
+struct SubscriptionGuard {
+    forwarder: Forwarder,
+    uuid: Uuid,
+    stream: &'static str,
+}
+
+impl Drop for SubscriptionGuard {
+    fn drop(&mut self) {
+        self.forwarder
+            .remove_subscription(&self.uuid, self.stream, "client_disconnected");
+    }
+}
+
+#[derive(Clone)]
+struct Broadcasters {
+    packets: broadcast::Sender<Arc<PacketDispatch>>,
+    bundles: broadcast::Sender<Arc<BundleDispatch>>,
+}
+
+/// Fans bundles and packet batches out to validator subscriptions. A dispatcher task hands each
+/// item to a broadcast channel once and every subscription has its own task that copies items
+/// from the broadcast channel into its gRPC stream, so a slow validator only holds up itself.
+/// Bundles are dispatched in batches of up to [BUNDLE_BATCH_MAX_SIZE], waiting at most
+/// [BUNDLE_BATCH_MAX_LATENCY] for a batch to fill.
+#[derive(Clone)]
+pub struct Forwarder {
+    subscriptions: Arc<Mutex<HashMap<Uuid, Subscription>>>,
+    /// Taken by the dispatcher when it stops so no new subscriptions are accepted.
+    broadcasters: Arc<Mutex<Option<Broadcasters>>>,
+    routing_mode: Arc<RwLock<RoutingMode>>,
+    leader_schedule: Option<LeaderScheduleTracker>,
+    backpressure_policies: Arc<RwLock<PerValidator<BackpressurePolicy>>>,
+    /// Subscriptions keep a snapshot of their validator's filter, updated when this changes.
+    packet_filters: Arc<watch::Sender<PerValidator<PacketFilter>>>,
+    retries: Option<DeliveryRetries>,
+    recent_bundles: Arc<Mutex<VecDeque<RecentBundle>>>,
+    bundle_history: Arc<Mutex<DispatchHistory>>,
+    dispatcher: Arc<Mutex<Option<JoinHandle<()>>>>,
+}
+
+impl Forwarder {
+    /// Spawns the dispatcher on the current runtime. It runs until shutdown is requested or either
+    /// receiver disconnects. With `retry_deliveries`, bundles that don't reach a validator because
+    /// its subscription closed are sent again if it resubscribes within [BUNDLE_RETRY_WINDOW]
+    /// and, when the leader schedule is known, is still an upcoming leader.
+    pub fn start(
+        bundle_receiver: Receiver<BundleUuid>,
+        packet_receiver: Receiver<PacketBatch>,
+        shutdown_receiver: watch::Receiver<bool>,
+        routing_mode: RoutingMode,
+        leader_schedule: Option<LeaderScheduleTracker>,
+        backpressure_policy: BackpressurePolicy,
+        retry_deliveries: bool,
+    ) -> Self {
+        assert!(
+            routing_mode == RoutingMode::Broadcast || leader_schedule.is_some(),
+            "leader-only routing requires a leader schedule"
+        );
+
+        let (packets, _) = broadcast::channel(BROADCAST_CAPACITY);
+        let (bundles, _) = broadcast::channel(BROADCAST_CAPACITY);
+        let broadcasters = Broadcasters { packets, bundles };
+
+        let forwarder = Forwarder {
+            subscriptions: Arc::new(Mutex::new(HashMap::default())),
+            broadcasters: Arc::new(Mutex::new(Some(broadcasters.clone()))),
+            routing_mode: Arc::new(RwLock::new(routing_mode)),
+            leader_schedule,
+            backpressure_policies: Arc::new(RwLock::new(PerValidator::new(backpressure_policy))),
+            packet_filters: Arc::new(watch::channel(PerValidator::new(PacketFilter::default())).0),
+            retries: retry_deliveries.then(DeliveryRetries::default),
+            recent_bundles: Arc::new(Mutex::new(VecDeque::with_capacity(RECENT_BUNDLES_CAPACITY))),
+            bundle_history: Arc::new(Mutex::new(DispatchHistory::default())),
+            dispatcher: Arc::new(Mutex::new(None)),
+        };
+        let dispatcher = tokio::spawn(forwarder.clone().run_dispatcher(
+            bundle_receiver,
+            packet_receiver,
+            shutdown_receiver,
+            broadcasters,
+        ));
+        *forwarder.dispatcher.lock().unwrap() = Some(dispatcher);
+        forwarder
+    }
+
+    /// Waits for the dispatcher to drain its channels and exit. Only the first call waits; clones
+    /// share the same dispatcher.
+    pub async fn join(&self) -> Result<(), JoinError> {
+        let dispatcher = self.dispatcher.lock().unwrap().take();
+        match dispatcher {
+            Some(dispatcher) => dispatcher.await,
+            None => Ok(()),
+        }
+    }
+
+    pub fn routing_mode(&self) -> RoutingMode {
+        *self.routing_mode.read().unwrap()
+    }
+
+    pub fn set_routing_mode(&self, routing_mode: RoutingMode) -> Result<(), String> {
+        if routing_mode == RoutingMode::LeaderOnly && self.leader_schedule.is_none() {
+            return Err("leader-only routing requires a leader schedule".to_string());
+        }
+        info!("routing mode set to {}", routing_mode);
+        *self.routing_mode.write().unwrap() = routing_mode;
+        Ok(())
+    }
+
+    pub fn backpressure_policies(&self) -> PerValidator<BackpressurePolicy> {
+        self.backpressure_policies.read().unwrap().clone()
+    }
+
+    /// The policy applied to a validator's subscriptions when their queue is full.
+    pub fn backpressure_policy(&self, identity: Option<&Pubkey>) -> BackpressurePolicy {
+        self.backpressure_policies.read().unwrap().get(identity)
+    }
+
+    /// Sets the policy for one validator, or the default if `identity` is None. Takes effect on
+    /// existing subscriptions the next time their queue is full.
+    pub fn set_backpressure_policy(&self, identity: Option<Pubkey>, policy: BackpressurePolicy) {
+        info!(
+            "backpressure policy for {} set to {}",
+            identity.map_or("default".to_string(), |identity| identity.to_string()),
+            policy
+        );
+        self.backpressure_policies
+            .write()
+            .unwrap()
+            .set(identity, policy);
+    }
+
+    /// Removes a validator's policy override. Returns false if it had none.
+    pub fn clear_backpressure_policy(&self, identity: &Pubkey) -> bool {
+        self.backpressure_policies.write().unwrap().clear(identity)
+    }
+
+    pub fn packet_filters(&self) -> PerValidator<PacketFilter> {
+        self.packet_filters.borrow().clone()
+    }
+
+    /// The filter applied to packets sent to a validator.
+    pub fn packet_filter(&self, identity: Option<&Pubkey>) -> PacketFilter {
+        self.packet_filters.borrow().get(identity)
+    }
+
+    /// Sets the packet filter for one validator, or the default if `identity` is None. Takes
+    /// effect on the next packet batch each subscription handles after it sees the change.
+    pub fn set_packet_filter(
+        &self,
+        identity: Option<Pubkey>,
+        filter: PacketFilter,
+    ) -> Result<(), String> {
+        filter.validate()?;
+        if filter.leaders_only && self.leader_schedule.is_none() {
+            return Err("leader-only packet filtering requires a leader schedule".to_string());
+        }
+        info!(
+            "packet filter for {} set to {}",
+            identity.map_or("default".to_string(), |identity| identity.to_string()),
+            filter
+        );
+        self.packet_filters
+            .send_modify(|filters| filters.set(identity, filter));
+        Ok(())
+    }
+
+    /// Removes a validator's packet filter override. Returns false if it had none.
+    pub fn clear_packet_filter(&self, identity: &Pubkey) -> bool {
+        let mut cleared = false;
+        self.packet_filters
+            .send_modify(|filters| cleared = filters.clear(identity));
+        cleared
+    }
+
+    pub fn subscribe_packets(
+        &self,
+        identity: Option<Pubkey>,
+    ) -> Result<SubscriptionStream<SubscribePacketsResponse>, Status> {
+        let receiver = self
+            .broadcasters
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|broadcasters| broadcasters.packets.subscribe())
+            .ok_or_else(|| Status::unavailable("block engine shutting down"))?;
+        // packets aren't retried, so the retry sender is dropped straight away
+        let (_, retry_receiver) = unbounded_channel();
+        Ok(self.add_subscription(
+            Uuid::new_v4(),
+            identity,
+            receiver,
+            Vec::new(),
+            retry_receiver,
+        ))
+    }
+
+    pub fn subscribe_bundles(
+        &self,
+        identity: Option<Pubkey>,
+    ) -> Result<SubscriptionStream<SubscribeBundlesResponse>, Status> {
+        let receiver = self
+            .broadcasters
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|broadcasters| broadcasters.bundles.subscribe())
+            .ok_or_else(|| Status::unavailable("block engine shutting down"))?;
+        let uuid = Uuid::new_v4();
+        let (retry_sender, retry_receiver) = unbounded_channel();
+        let retried = identity
+            .map(|identity| self.take_retries(uuid, &identity, retry_sender))
+            .unwrap_or_default();
+        Ok(self.add_subscription(uuid, identity, receiver, retried, retry_receiver))
+    }
+
+    /// Lists the active packet and bundle subscriptions.
+    pub fn subscriptions(&self) -> Vec<SubscriptionInfo> {
+        self.subscriptions
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(uuid, sub)| SubscriptionInfo {
+                uuid: *uuid,
+                stream: sub.stream,
+                identity: sub.identity,
+                age: sub.connected_at.elapsed(),
+                backpressure_policy: self.backpressure_policy(sub.identity.as_ref()),
+            })
+            .collect()
+    }
+
+    /// Identities of the validators with a bundle subscription.
+    pub fn connected_validators(&self) -> HashSet<Pubkey> {
+        self.subscriptions
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|sub| sub.stream == BundleDispatch::STREAM)
+            .filter_map(|sub| sub.identity)
+            .collect()
+    }
+
+    /// Closes a packet or bundle subscription. Returns false if no subscription has the uuid.
+    pub fn disconnect(&self, uuid: &Uuid) -> bool {
+        let disconnect_sender = self
+            .subscriptions
+            .lock()
+            .unwrap()
+            .get_mut(uuid)
+            .and_then(|sub| sub.disconnect_sender.take());
+        match disconnect_sender {
+            Some(disconnect_sender) => {
+                info!("disconnecting subscription uuid: {:?}", uuid);
+                let _ = disconnect_sender.send((
+                    Status::aborted("disconnected by block engine operator"),
+                    "operator_disconnect",
+                ));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The most recently forwarded bundles, oldest first.
+    pub fn recent_bundles(&self) -> Vec<ForwardedBundle> {
+        self.recent_bundles
+            .lock()
+            .unwrap()
+            .iter()
+            .map(RecentBundle::snapshot)
+            .collect()
+    }
+
+    /// A recently forwarded bundle and its delivery record, if it's still kept.
+    pub fn bundle(&self, uuid: &str) -> Option<ForwardedBundle> {
+        self.recent_bundles
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|bundle| bundle.uuid == uuid)
+            .map(RecentBundle::snapshot)
+    }
+
+    /// Makes the subscription `uuid` the one that receives a validator's retries from now on and
+    /// takes the bundles held for it that may still be retried, batched for dispatch. Both happen
+    /// under one lock, so bundles its previous subscription drops while being replaced are sent
+    /// on the retry sender rather than held after the held ones were taken.
+    fn take_retries(
+        &self,
+        uuid: Uuid,
+        identity: &Pubkey,
+        retry_sender: UnboundedSender<Arc<BundleDispatch>>,
+    ) -> Vec<Arc<BundleDispatch>> {
+        let entries = match &self.retries {
+            Some(retries) => {
+                let mut retries = retries.lock().unwrap();
+                retries.live.insert(*identity, (uuid, retry_sender));
+                retries.held.remove(identity)
+            }
+            None => None,
+        };
+        let entries = match entries {
+            Some(entries) => entries,
+            None => return Vec::new(),
+        };
+        if !self.may_retry(identity) {
+            return Vec::new();
+        }
+
+        let now = Instant::now();
+        let entries: Vec<RetryEntry> = entries
+            .into_iter()
+            .filter(|entry| entry.expires_at > now)
+            .collect();
+        if entries.is_empty() {
+            return Vec::new();
+        }
+        info!(
+            "retrying {} bundles for validator {}",
+            entries.len(),
+            identity
+        );
+        BUNDLE_RETRIES.inc_by(entries.len() as u64);
+
+        let mut entries = entries.into_iter().peekable();
+        let mut dispatches = Vec::new();
+        while entries.peek().is_some() {
+            let chunk: Vec<RetryEntry> = entries.by_ref().take(BUNDLE_BATCH_MAX_SIZE).collect();
+            let retry_until = chunk.iter().map(|entry| entry.expires_at).min().unwrap();
+            dispatches.push(Arc::new(BundleDispatch {
+                seq: 0,
+                bundles: chunk
+                    .into_iter()
+                    .map(|entry| (entry.bundle, entry.stats))
+                    .collect(),
+                recipients: None,
+                dispatched_at: now,
+                retries: self.retries.clone(),
+                retry_until,
+            }));
+        }
+        dispatches
+    }
+
+    /// Whether a validator may still use retried bundles: it's an upcoming leader, or the leader
+    /// schedule isn't known.
+    fn may_retry(&self, identity: &Pubkey) -> bool {
+        self.leader_schedule
+            .as_ref()
+            .and_then(|leader_schedule| leader_schedule.upcoming_leaders(LEADER_LOOKAHEAD_SLOTS))
+            .map_or(true, |leaders| leaders.contains(identity))
+    }
+
+    async fn run_dispatcher(
+        self,
+        mut bundle_receiver: Receiver<BundleUuid>,
+        mut packet_receiver: Receiver<PacketBatch>,
+        mut shutdown_receiver: watch::Receiver<bool>,
+        broadcasters: Broadcasters,
+    ) {
+        let mut bundle_batch: Option<BundleBatch> = None;
+        loop {
+            let batch_deadline =
+                TokioInstant::from_std(bundle_batch.as_ref().map_or_else(Instant::now, |batch| {
+                    batch.started_at + BUNDLE_BATCH_MAX_LATENCY
+                }));
+            tokio::select! {
+                result = shutdown_receiver.changed() => {
+                    if result.is_err() || *shutdown_receiver.borrow() {
+                        info!("shutdown requested, draining forwarder channels");
+                        break;
+                    }
+                }
+                maybe_packet_batch = packet_receiver.recv() => {
+                    if let Some(packet_batch) = maybe_packet_batch {
+                        self.dispatch_packets(&broadcasters, packet_batch);
+                    } else {
+                        warn!("packet_receiver disconnected, exiting");
+                        break;
+                    }
+                }
+                maybe_bundle = bundle_receiver.recv() => {
+                    if let Some(bundle) = maybe_bundle {
+                        if self.add_to_batch(&mut bundle_batch, bundle) {
+                            self.dispatch_bundles(&broadcasters, bundle_batch.take());
+                        }
+                    } else {
+                        warn!("bundle_receiver disconnected, exiting");
+                        break;
+                    }
+                }
+                _ = sleep_until(batch_deadline), if bundle_batch.is_some() => {
+                    self.dispatch_bundles(&broadcasters, bundle_batch.take());
+                }
+            }
+        }
+
+        self.broadcasters.lock().unwrap().take();
+
+        // forward anything that was accepted before shutdown was requested
+        while let Ok(packet_batch) = packet_receiver.try_recv() {
+            self.dispatch_packets(&broadcasters, packet_batch);
+        }
+        while let Ok(bundle) = bundle_receiver.try_recv() {
+            if self.add_to_batch(&mut bundle_batch, bundle) {
+                self.dispatch_bundles(&broadcasters, bundle_batch.take());
+            }
+        }
+        self.dispatch_bundles(&broadcasters, bundle_batch.take());
+
+        // dropping the last senders closes every subscription once it has sent what's queued
+        drop(broadcasters);
+    }
+
+    fn dispatch_packets(&self, broadcasters: &Broadcasters, batch: PacketBatch) {
+        let leaders = match &self.leader_schedule {
+            Some(leader_schedule) if self.filters_on_leaders() => {
+                leader_schedule.upcoming_leaders(LEADER_LOOKAHEAD_SLOTS)
+            }
+            _ => None,
+        };
+
+        // send only fails when there are no subscriptions
+        let _ = broadcasters.packets.send(Arc::new(PacketDispatch {
+            batch,
+            leaders,
+            dispatched_at: Instant::now(),
+        }));
+    }
+
+    fn filters_on_leaders(&self) -> bool {
+        let packet_filters = self.packet_filters.borrow();
+        packet_filters.default.leaders_only
+            || packet_filters
+                .overrides
+                .values()
+                .any(|filter| filter.leaders_only)
+    }
+
+    /// Adds a bundle to the batch, starting one if needed. Returns true once the batch is full.
+    fn add_to_batch(&self, bundle_batch: &mut Option<BundleBatch>, bundle: BundleUuid) -> bool {
+        let stats = Arc::new(ForwardStats::default());
+        let bundles = &mut bundle_batch
+            .get_or_insert_with(|| BundleBatch {
+                bundles: Vec::with_capacity(BUNDLE_BATCH_MAX_SIZE),
+                started_at: Instant::now(),
+            })
+            .bundles;
+        bundles.push((bundle, stats));
+        bundles.len() >= BUNDLE_BATCH_MAX_SIZE
+    }
+
+    fn dispatch_bundles(&self, broadcasters: &Broadcasters, bundle_batch: Option<BundleBatch>) {
+        let bundle_batch = match bundle_batch {
+            Some(bundle_batch) => bundle_batch,
+            None => return,
+        };
+        let recipients = match (self.routing_mode(), &self.leader_schedule) {
+            // broadcast while the upcoming leaders aren't known rather than drop the batch
+            (RoutingMode::LeaderOnly, Some(leader_schedule)) => {
+                leader_schedule.upcoming_leaders(LEADER_LOOKAHEAD_SLOTS)
+            }
+            _ => None,
+        };
+
+        BUNDLE_BATCH_SIZE.observe(bundle_batch.bundles.len() as f64);
+        self.record_forwarded(&bundle_batch.bundles);
+        let seq = {
+            let mut history = self.bundle_history.lock().unwrap();
+            let seq = history.next_seq;
+            history.next_seq += 1;
+            if history.batches.len() == DISPATCH_HISTORY_CAPACITY {
+                history.batches.pop_front();
+            }
+            history.batches.push_back(DispatchedBatch {
+                seq,
+                recipients: recipients.clone(),
+                stats: bundle_batch
+                    .bundles
+                    .iter()
+                    .map(|(_, stats)| stats.clone())
+                    .collect(),
+            });
+            seq
+        };
+        // send only fails when there are no subscriptions
+        let _ = broadcasters.bundles.send(Arc::new(BundleDispatch {
+            seq,
+            bundles: bundle_batch.bundles,
+            recipients,
+            dispatched_at: bundle_batch.started_at,
+            retries: self.retries.clone(),
+            retry_until: bundle_batch.started_at + BUNDLE_RETRY_WINDOW,
+        }));
+    }
+
+    /// Records a batch's bundles as forwarded, taking the lock once per batch rather than once
+    /// per bundle.
+    fn record_forwarded(&self, bundles: &[(BundleUuid, Arc<ForwardStats>)]) {
+        let forwarded_at = SystemTime::now();
+        let mut recent_bundles = self.recent_bundles.lock().unwrap();
+        for (bundle, stats) in bundles {
+            if recent_bundles.len() == RECENT_BUNDLES_CAPACITY {
+                recent_bundles.pop_front();
+            }
+            recent_bundles.push_back(RecentBundle {
+                uuid: bundle.uuid.clone(),
+                forwarded_at,
+                num_packets: bundle.bundle.as_ref().map_or(0, |b| b.packets.len()),
+                stats: stats.clone(),
+            });
+        }
+    }
+
+    fn add_subscription<D: Dispatch>(
+        &self,
+        uuid: Uuid,
+        identity: Option<Pubkey>,
+        receiver: broadcast::Receiver<Arc<D>>,
+        initial: Vec<Arc<D>>,
+        retry_receiver: UnboundedReceiver<Arc<D>>,
+    ) -> SubscriptionStream<D::Response> {
+        let (sender, stream_receiver) = channel(STREAM_CHANNEL_CAPACITY);
+        let (disconnect_sender, disconnect_receiver) = oneshot::channel();
+
+        info!(
+            "adding {} subscription uuid: {:?} identity: {:?}",
+            D::STREAM,
+            uuid,
+            identity
+        );
+        {
+            let mut subscriptions = self.subscriptions.lock().unwrap();
+            // a validator gets one subscription per stream; a new one takes over from the old
+            if let Some(identity) = identity {
+                let replaced = subscriptions
+                    .iter_mut()
+                    .filter(|(_, sub)| sub.stream == D::STREAM && sub.identity == Some(identity));
+                for (replaced_uuid, sub) in replaced {
+                    if let Some(disconnect_sender) = sub.disconnect_sender.take() {
+                        info!(
+                            "{} subscription uuid: {:?} replaced by uuid: {:?} identity: {}",
+                            D::STREAM,
+                            replaced_uuid,
+                            uuid,
+                            identity
+                        );
+                        let _ = disconnect_sender.send((
+                            Status::aborted("replaced by a newer subscription"),
+                            "replaced",
+                        ));
+                    }
+                }
+            }
+            subscriptions.insert(
+                uuid,
+                Subscription {
+                    stream: D::STREAM,
+                    identity,
+                    connected_at: Instant::now(),
+                    disconnect_sender: Some(disconnect_sender),
+                },
+            );
+        }
+        CONNECTED_VALIDATORS.with_label_values(&[D::STREAM]).inc();
+        CONNECTION_EVENTS
+            .with_label_values(&[D::STREAM, "connected"])
+            .inc();
+
+        tokio::spawn(self.clone().run_subscription(
+            Subscriber { uuid, identity },
+            receiver,
+            initial,
+            retry_receiver,
+            sender,
+            disconnect_receiver,
+        ));
+        SubscriptionStream {
+            inner: stream_receiver,
+            _guard: SubscriptionGuard {
+                forwarder: self.clone(),
+                uuid,
+                stream: D::STREAM,
+            },
+        }
+    }
+
+    /// Removes a subscription from the registry, which also stops its task if it's still running.
+    /// Returns false if it was already removed.
+    fn remove_subscription(&self, uuid: &Uuid, stream: &'static str, reason: &'static str) -> bool {
+        let sub = match self.subscriptions.lock().unwrap().remove(uuid) {
+            Some(sub) => sub,
+            None => return false,
+        };
+        if let (Some(retries), Some(identity)) = (&self.retries, sub.identity) {
+            let mut retries = retries.lock().unwrap();
+            // a newer subscription may already have taken over the validator's retries
+            if retries
+                .live
+                .get(&identity)
+                .map_or(false, |(live_uuid, _)| live_uuid == uuid)
+            {
+                retries.live.remove(&identity);
+            }
+        }
+        CONNECTED_VALIDATORS.with_label_values(&[stream]).dec();
+        CONNECTION_EVENTS.with_label_values(&[stream, reason]).inc();
+        info!(
+            "removed {} subscription uuid: {:?} reason: {}",
+            stream, uuid, reason
+        );
+        true
+    }
+
+    async fn run_subscription<D: Dispatch>(
+        self,
+        subscriber: Subscriber,
+        mut receiver: broadcast::Receiver<Arc<D>>,
+        initial: Vec<Arc<D>>,
+        mut retry_receiver: UnboundedReceiver<Arc<D>>,
+        sender: Sender<Outgoing<D::Response>>,
+        mut disconnect_receiver: oneshot::Receiver<(Status, &'static str)>,
+    ) {
+        let mut packet_filters = self.packet_filters.subscribe();
+        let mut packet_filter = packet_filters
+            .borrow_and_update()
+            .get(subscriber.identity.as_ref());
+        let mut queue = VecDeque::with_capacity(SUBSCRIPTION_QUEUE_CAPACITY);
+        for item in initial {
+            if let Some(response) = item.response(&packet_filter, subscriber.identity.as_ref()) {
+                item.on_retried(&subscriber);
+                queue.push_back((item, response));
+            }
+        }
+        let mut consecutive_full = 0;
+        // items skipped since the last one received, recorded once the next one shows where
+        // they were
+        let mut lagged = 0;
+        let mut last_sent = TokioInstant::now();
+
+        let (close_status, close_reason) = loop {
+            tokio::select! {
+                status = &mut disconnect_receiver => match status {
+                    Ok((status, reason)) => break (Some(status), reason),
+                    // the stream's guard removed the subscription when the validator went away
+                    Err(_) => break (None, "client_disconnected"),
+                },
+                permit = sender.reserve(), if !queue.is_empty() => match permit {
+                    Ok(permit) => {
+                        let (item, response) = queue.pop_front().unwrap();
+                        Self::send(&subscriber, permit, item, response);
+                        last_sent = TokioInstant::now();
+                    }
+                    Err(_) => break (None, "client_disconnected"),
+                },
+                _ = sleep_until(last_sent + HEARTBEAT_INTERVAL), if queue.is_empty() => {
+                    if let Some(heartbeat) = D::heartbeat() {
+                        match sender.try_send(Outgoing::untracked(heartbeat)) {
+                            Ok(()) => HEARTBEATS_SENT.with_label_values(&[D::STREAM]).inc(),
+                            Err(TrySendError::Full(_)) => {}
+                            Err(TrySendError::Closed(_)) => break (None, "client_disconnected"),
+                        }
+                    }
+                    last_sent = TokioInstant::now();
+                }
+                Ok(()) = packet_filters.changed() => {
+                    packet_filter = packet_filters
+                        .borrow_and_update()
+                        .get(subscriber.identity.as_ref());
+                }
+                Some(item) = retry_receiver.recv() => {
+                    let identity = match subscriber.identity {
+                        Some(identity) if self.may_retry(&identity) => identity,
+                        _ => continue,
+                    };
+                    let response = match item.response(&packet_filter, Some(&identity)) {
+                        Some(response) => response,
+                        None => continue,
+                    };
+                    item.on_retried(&subscriber);
+                    let enqueue = self.enqueue(
+                        &subscriber,
+                        &mut queue,
+                        item,
+                        response,
+                        &mut consecutive_full,
+                        &sender,
+                    );
+                    match enqueue.await {
+                        Ok(()) => {}
+                        Err(Some(status)) => break (Some(status), "backpressure"),
+                        Err(None) => break (None, "client_disconnected"),
+                    }
+                }
+                result = receiver.recv() => match result {
+                    Ok(item) => {
+                        if lagged > 0 {
+                            D::on_skipped(&self, &subscriber, item.seq(), lagged);
+                            lagged = 0;
+                        }
+                        let identity = subscriber.identity.as_ref();
+                        let response = match item.response(&packet_filter, identity) {
+                            Some(response) => response,
+                            None => continue,
+                        };
+                        let enqueue = self.enqueue(
+                            &subscriber,
+                            &mut queue,
+                            item,
+                            response,
+                            &mut consecutive_full,
+                            &sender,
+                        );
+                        match enqueue.await {
+                            Ok(()) => {}
+                            Err(Some(status)) => break (Some(status), "backpressure"),
+                            Err(None) => break (None, "client_disconnected"),
+                        }
+                    }
+                    Err(RecvError::Lagged(skipped)) => {
+                        SUBSCRIPTION_LAGGED.with_label_values(&[D::STREAM]).inc_by(skipped);
+                        lagged += skipped;
+                        warn!(
+                            "{} subscription lagged uuid: {:?} skipped: {}",
+                            D::STREAM, subscriber.uuid, skipped
+                        );
+                    }
+                    Err(RecvError::Closed) => {
+                        Self::flush(&subscriber, &mut queue, &sender).await;
+                        break (Some(Status::unavailable("block engine shutting down")), "shutdown");
+                    }
+                }
+            }
+        };
+
+        if let Some(status) = close_status {
+            let _ = sender.try_send(Outgoing::status(status));
+        }
+        for (item, response) in queue {
+            item.on_dropped(&subscriber, &response, "subscription_closed");
+        }
+        // retries handed over after this are held for the validator's next subscription
+        retry_receiver.close();
+        while let Ok(item) = retry_receiver.try_recv() {
+            if let Some(response) = item.response(&packet_filter, subscriber.identity.as_ref()) {
+                item.on_dropped(&subscriber, &response, "subscription_closed");
+            }
+        }
+
+        self.remove_subscription(&subscriber.uuid, D::STREAM, close_reason);
+    }
+
+    /// Queues an item for the validator, applying its backpressure policy if the queue is full.
+    async fn enqueue<D: Dispatch>(
+        &self,
+        subscriber: &Subscriber,
+        queue: &mut VecDeque<(Arc<D>, D::Response)>,
+        item: Arc<D>,
+        response: D::Response,
+        consecutive_full: &mut u32,
+        sender: &Sender<Outgoing<D::Response>>,
+    ) -> Result<(), Option<Status>> {
+        if queue.len() < SUBSCRIPTION_QUEUE_CAPACITY {
+            *consecutive_full = 0;
+            queue.push_back((item, response));
+            return Ok(());
+        }
+        *consecutive_full += 1;
+        self.apply_backpressure(subscriber, queue, item, response, *consecutive_full, sender)
+            .await
+    }
+
+    /// Handles an item that found the subscription's queue full. Returns Err with the status to
+    /// send, if any, when the subscription should be closed.
+    async fn apply_backpressure<D: Dispatch>(
+        &self,
+        subscriber: &Subscriber,
+        queue: &mut VecDeque<(Arc<D>, D::Response)>,
+        item: Arc<D>,
+        response: D::Response,
+        consecutive_full: u32,
+        sender: &Sender<Outgoing<D::Response>>,
+    ) -> Result<(), Option<Status>> {
+        CHANNEL_FULL.with_label_values(&[D::STREAM]).inc();
+        match self.backpressure_policy(subscriber.identity.as_ref()) {
+            BackpressurePolicy::DropNewest => item.on_dropped(subscriber, &response, "queue_full"),
+            BackpressurePolicy::DropOldest => {
+                if let Some((oldest, oldest_response)) = queue.pop_front() {
+                    oldest.on_dropped(subscriber, &oldest_response, "evicted");
+                }
+                queue.push_back((item, response));
+            }
+            BackpressurePolicy::Disconnect {
+                max_consecutive_full,
+            } => {
+                item.on_dropped(subscriber, &response, "queue_full");
+                if consecutive_full >= max_consecutive_full {
+                    warn!(
+                        "{} subscription too slow, disconnecting uuid: {:?}",
+                        D::STREAM,
+                        subscriber.uuid
+                    );
+                    BACKPRESSURE_DISCONNECTS
+                        .with_label_values(&[D::STREAM])
+                        .inc();
+                    return Err(Some(Status::resource_exhausted(
+                        "subscription fell too far behind",
+                    )));
+                }
+            }
+            BackpressurePolicy::Block { timeout: duration } => {
+                match timeout(duration, sender.reserve()).await {
+                    Ok(Ok(permit)) => {
+                        let (oldest, oldest_response) = queue.pop_front().unwrap();
+                        Self::send(subscriber, permit, oldest, oldest_response);
+                        queue.push_back((item, response));
+                    }
+                    Ok(Err(_)) => {
+                        item.on_dropped(subscriber, &response, "channel_closed");
+                        return Err(None);
+                    }
+                    Err(_) => item.on_dropped(subscriber, &response, "block_timeout"),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends what's left in a closing subscription's queue, giving up after
+    /// [CLOSE_FLUSH_TIMEOUT] so a stalled validator can't hold up shutdown.
+    async fn flush<D: Dispatch>(
+        subscriber: &Subscriber,
+        queue: &mut VecDeque<(Arc<D>, D::Response)>,
+        sender: &Sender<Outgoing<D::Response>>,
+    ) {
+        let flush = async {
+            while !queue.is_empty() {
+                match sender.reserve().await {
+                    Ok(permit) => {
+                        let (item, response) = queue.pop_front().unwrap();
+                        Self::send(subscriber, permit, item, response);
+                    }
+                    Err(_) => break,
+                }
+            }
+        };
+        if timeout(CLOSE_FLUSH_TIMEOUT, flush).await.is_err() {
+            warn!(
+                "{} subscription didn't drain before closing uuid: {:?}",
+                D::STREAM,
+                subscriber.uuid
+            );
+        }
+    }
+
+    fn send<D: Dispatch>(
+        subscriber: &Subscriber,
+        permit: Permit<'_, Outgoing<D::Response>>,
+        item: Arc<D>,
+        response: D::Response,
+    ) {
+        item.on_sent(&response);
+        let on_delivery = item.clone().on_delivery(*subscriber);
+        permit.send(Outgoing {
+            message: Ok(response),
+            on_delivery,
+        });
+        FORWARD_LATENCY
+            .with_label_values(&[D::STREAM])
+            .observe(item.dispatched_at().elapsed().as_secs_f64());
+        debug!("{} forwarded uuid: {:?}", D::STREAM, subscriber.uuid);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_stream::StreamExt;
+    use tonic::Code;
+
+    struct TestForwarder {
+        forwarder: Forwarder,
+        bundle_sender: Sender<BundleUuid>,
+        _packet_sender: Sender<PacketBatch>,
+        _shutdown_sender: watch::Sender<bool>,
+    }
+
+    fn start_forwarder(retry_deliveries: bool) -> TestForwarder {
+        let (bundle_sender, bundle_receiver) = channel(100);
+        let (packet_sender, packet_receiver) = channel(100);
+        let (shutdown_sender, shutdown_receiver) = watch::channel(false);
+        let forwarder = Forwarder::start(
+            bundle_receiver,
+            packet_receiver,
+            shutdown_receiver,
+            RoutingMode::Broadcast,
+            None,
+            BackpressurePolicy::DropNewest,
+            retry_deliveries,
+        );
+        TestForwarder {
+            forwarder,
+            bundle_sender,
+            _packet_sender: packet_sender,
+            _shutdown_sender: shutdown_sender,
+        }
+    }
+
+    /// Waits until the bundle has been put on a subscription's stream.
+    async fn wait_until_sent(forwarder: &Forwarder, uuid: &str) {
+        while forwarder
+            .bundle(uuid)
+            .map_or(true, |bundle| bundle.validators_sent == 0)
+        {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+    }
+
+    fn bundle() -> BundleUuid {
+        BundleUuid {
+            uuid: Uuid::new_v4().to_string(),
+            ..BundleUuid::default()
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_bundle_batch_flushed_when_full() {
+        let test = start_forwarder(false);
+        let mut stream = test
+            .forwarder
+            .subscribe_bundles(Some(Pubkey::new_unique()))
+            .unwrap();
+
+        let started_at = TokioInstant::now();
+        for _ in 0..BUNDLE_BATCH_MAX_SIZE + 1 {
+            test.bundle_sender.try_send(bundle()).unwrap();
+        }
+        // a full batch goes out without waiting
+        let response = stream.next().await.unwrap().unwrap();
+        assert_eq!(response.bundles.len(), BUNDLE_BATCH_MAX_SIZE);
+        assert!(started_at.elapsed() < BUNDLE_BATCH_MAX_LATENCY);
+
+        // the bundle left over waits out the batch latency on its own
+        let response = stream.next().await.unwrap().unwrap();
+        assert_eq!(response.bundles.len(), 1);
+        assert!(started_at.elapsed() >= BUNDLE_BATCH_MAX_LATENCY);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_bundle_batch_flushed_after_max_latency() {
+        let test = start_forwarder(false);
+        let mut stream = test
+            .forwarder
+            .subscribe_bundles(Some(Pubkey::new_unique()))
+            .unwrap();
+
+        let started_at = TokioInstant::now();
+        let bundles = [bundle(), bundle()];
+        for bundle in &bundles {
+            test.bundle_sender.try_send(bundle.clone()).unwrap();
+        }
+        let response = stream.next().await.unwrap().unwrap();
+        assert_eq!(response.bundles, bundles);
+        assert!(started_at.elapsed() >= BUNDLE_BATCH_MAX_LATENCY);
+        assert!(started_at.elapsed() < HEARTBEAT_INTERVAL);
+    }
+
+    #[tokio::test]
+    async fn test_dropping_stream_removes_subscription() {
+        let test = start_forwarder(true);
+        let identity = Pubkey::new_unique();
+        let bundles = test.forwarder.subscribe_bundles(Some(identity)).unwrap();
+        let packets = test.forwarder.subscribe_packets(Some(identity)).unwrap();
+        assert_eq!(test.forwarder.subscriptions().len(), 2);
+        assert_eq!(
+            test.forwarder.connected_validators(),
+            HashSet::from([identity])
+        );
+
+        drop(bundles);
+        let subscriptions = test.forwarder.subscriptions();
+        assert_eq!(subscriptions.len(), 1);
+        assert_eq!(subscriptions[0].stream, PacketDispatch::STREAM);
+        assert!(test.forwarder.connected_validators().is_empty());
+        // retries for the validator are held until it resubscribes
+        let retries = test.forwarder.retries.as_ref().unwrap();
+        assert!(!retries.lock().unwrap().live.contains_key(&identity));
+
+        drop(packets);
+        assert!(test.forwarder.subscriptions().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_new_subscription_takes_over_identity() {
+        let test = start_forwarder(false);
+        let identity = Pubkey::new_unique();
+        let mut first = test.forwarder.subscribe_bundles(Some(identity)).unwrap();
+        let mut second = test.forwarder.subscribe_bundles(Some(identity)).unwrap();
+
+        // the first stream is closed with a status and then ends
+        let status = first.next().await.unwrap().unwrap_err();
+        assert_eq!(status.code(), Code::Aborted);
+        assert!(first.next().await.is_none());
+        let subscriptions = test.forwarder.subscriptions();
+        assert_eq!(subscriptions.len(), 1);
+        assert_eq!(
+            test.forwarder.connected_validators(),
+            HashSet::from([identity])
+        );
+
+        let bundle = bundle();
+        test.bundle_sender.try_send(bundle.clone()).unwrap();
+        let response = second.next().await.unwrap().unwrap();
+        assert_eq!(response.bundles, [bundle.clone()]);
+        let forwarded = test.forwarder.bundle(&bundle.uuid).unwrap();
+        assert_eq!(forwarded.validators_sent, 1);
+        assert_eq!(forwarded.events.len(), 1);
+        assert_eq!(forwarded.events[0].subscription, subscriptions[0].uuid);
+    }
+
+    #[tokio::test]
+    async fn test_undelivered_bundle_retried_within_window() {
+        let test = start_forwarder(true);
+        let identity = Pubkey::new_unique();
+        let first = test.forwarder.subscribe_bundles(Some(identity)).unwrap();
+        let bundle = bundle();
+        test.bundle_sender.try_send(bundle.clone()).unwrap();
+        wait_until_sent(&test.forwarder, &bundle.uuid).await;
+
+        // the validator goes away before reading the bundle and comes back
+        drop(first);
+        let mut second = test.forwarder.subscribe_bundles(Some(identity)).unwrap();
+        let response = second.next().await.unwrap().unwrap();
+        assert_eq!(response.bundles, [bundle.clone()]);
+
+        let forwarded = test.forwarder.bundle(&bundle.uuid).unwrap();
+        assert_eq!(forwarded.validators_sent, 2);
+        assert_eq!(forwarded.validators_dropped, 1);
+        assert_eq!(forwarded.validators_delivered, 1);
+        let kinds: Vec<_> = forwarded.events.iter().map(|event| &event.kind).collect();
+        assert!(matches!(
+            kinds[..],
+            [
+                BundleEventKind::Dropped {
+                    reason: "undelivered"
+                },
+                BundleEventKind::Retried,
+                BundleEventKind::Delivered,
+            ]
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_undelivered_bundle_dropped_after_window() {
+        let test = start_forwarder(true);
+        let identity = Pubkey::new_unique();
+        let first = test.forwarder.subscribe_bundles(Some(identity)).unwrap();
+        let bundle = bundle();
+        test.bundle_sender.try_send(bundle.clone()).unwrap();
+        wait_until_sent(&test.forwarder, &bundle.uuid).await;
+        drop(first);
+
+        // the retry window is measured on the system clock
+        std::thread::sleep(BUNDLE_RETRY_WINDOW);
+        let mut second = test.forwarder.subscribe_bundles(Some(identity)).unwrap();
+        assert!(timeout(Duration::from_millis(100), second.next())
+            .await
+            .is_err());
+
+        let retries = test.forwarder.retries.as_ref().unwrap();
+        assert!(!retries.lock().unwrap().held.contains_key(&identity));
+        let forwarded = test.forwarder.bundle(&bundle.uuid).unwrap();
+        assert_eq!(forwarded.validators_sent, 1);
+        assert_eq!(forwarded.validators_dropped, 1);
+        assert_eq!(forwarded.events.len(), 1);
+    }
+}
@@ -1 +1,7 @@
+pub mod backpressure;
+pub mod forwarder;
+pub mod metrics;
+pub mod packet_filter;
+pub mod per_validator;
+pub mod routing;
 pub mod server;
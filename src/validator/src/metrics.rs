@@ -0,0 +1,127 @@
+use lazy_static::lazy_static;
+use prometheus::{
+    register_histogram, register_histogram_vec, register_int_counter, register_int_counter_vec,
+    register_int_gauge_vec, Histogram, HistogramVec, IntCounter, IntCounterVec, IntGaugeVec,
+};
+
+lazy_static! {
+    /// Bundles sent to validators, counted once per subscription.
+    pub static ref BUNDLES_FORWARDED: IntCounter = register_int_counter!(
+        "block_engine_bundles_forwarded_total",
+        "Bundles sent to validator subscriptions"
+    )
+    .unwrap();
+
+    /// Bundles sent to validators that were or weren't taken off the stream before it closed.
+    pub static ref BUNDLE_DELIVERIES: IntCounterVec = register_int_counter_vec!(
+        "block_engine_bundle_deliveries_total",
+        "Bundles sent to validator subscriptions, by whether they reached the validator",
+        &["result"]
+    )
+    .unwrap();
+
+    /// Bundles queued again on a validator's new subscription after failing to reach it.
+    pub static ref BUNDLE_RETRIES: IntCounter = register_int_counter!(
+        "block_engine_bundle_retries_total",
+        "Bundles resent to a validator after failing to reach its previous subscription"
+    )
+    .unwrap();
+
+    /// Bundles that could not be sent to a validator subscription, by reason.
+    pub static ref BUNDLES_DROPPED: IntCounterVec = register_int_counter_vec!(
+        "block_engine_bundles_dropped_total",
+        "Bundles that could not be sent to a validator subscription",
+        &["reason"]
+    )
+    .unwrap();
+
+    /// Packets sent to validators, counted once per subscription.
+    pub static ref PACKETS_FORWARDED: IntCounter = register_int_counter!(
+        "block_engine_packets_forwarded_total",
+        "Packets sent to validator subscriptions"
+    )
+    .unwrap();
+
+    /// Packets a validator's packet filter kept from it, by reason.
+    pub static ref PACKETS_FILTERED: IntCounterVec = register_int_counter_vec!(
+        "block_engine_packets_filtered_total",
+        "Packets kept from a validator by its packet filter",
+        &["reason"]
+    )
+    .unwrap();
+
+    /// Packets that could not be sent to a validator subscription, by reason.
+    pub static ref PACKETS_DROPPED: IntCounterVec = register_int_counter_vec!(
+        "block_engine_packets_dropped_total",
+        "Packets that could not be sent to a validator subscription",
+        &["reason"]
+    )
+    .unwrap();
+
+    /// Items that found a validator subscription's queue full and were handled by its
+    /// backpressure policy.
+    pub static ref CHANNEL_FULL: IntCounterVec = register_int_counter_vec!(
+        "block_engine_subscription_channel_full_total",
+        "Items that found a validator subscription's queue full",
+        &["stream"]
+    )
+    .unwrap();
+
+    /// Validator subscriptions opened and closed, by close reason.
+    pub static ref CONNECTION_EVENTS: IntCounterVec = register_int_counter_vec!(
+        "block_engine_validator_connection_events_total",
+        "Validator subscriptions opened (connected) and closed, by reason",
+        &["stream", "event"]
+    )
+    .unwrap();
+
+    /// Heartbeats sent on idle validator packet streams.
+    pub static ref HEARTBEATS_SENT: IntCounterVec = register_int_counter_vec!(
+        "block_engine_heartbeats_sent_total",
+        "Heartbeats sent on idle validator streams",
+        &["stream"]
+    )
+    .unwrap();
+
+    /// Subscriptions closed by the disconnect backpressure policy.
+    pub static ref BACKPRESSURE_DISCONNECTS: IntCounterVec = register_int_counter_vec!(
+        "block_engine_backpressure_disconnects_total",
+        "Validator subscriptions closed for falling too far behind",
+        &["stream"]
+    )
+    .unwrap();
+
+    /// Items a subscription skipped because it fell further behind the dispatcher than the
+    /// broadcast channel holds.
+    pub static ref SUBSCRIPTION_LAGGED: IntCounterVec = register_int_counter_vec!(
+        "block_engine_subscription_lagged_total",
+        "Items a validator subscription skipped after falling behind the dispatcher",
+        &["stream"]
+    )
+    .unwrap();
+
+    /// Active validator subscriptions.
+    pub static ref CONNECTED_VALIDATORS: IntGaugeVec = register_int_gauge_vec!(
+        "block_engine_connected_validators",
+        "Active validator subscriptions",
+        &["stream"]
+    )
+    .unwrap();
+
+    /// Bundles sent to validators per response.
+    pub static ref BUNDLE_BATCH_SIZE: Histogram = register_histogram!(
+        "block_engine_bundle_batch_size",
+        "Bundles per response sent to validator subscriptions",
+        vec![1.0, 2.0, 4.0, 8.0, 16.0, 32.0]
+    )
+    .unwrap();
+
+    /// Time from a bundle or packet batch being dispatched to it being handed to a subscription's
+    /// gRPC stream.
+    pub static ref FORWARD_LATENCY: HistogramVec = register_histogram_vec!(
+        "block_engine_forward_latency_seconds",
+        "Time from dispatching a bundle or packet batch to handing it to a validator stream",
+        &["stream"]
+    )
+    .unwrap();
+}
@@ -0,0 +1,140 @@
+use jito_protos::packet::{Packet, PacketBatch};
+use rand::Rng;
+use std::fmt;
+
+/// Limits the packets sent to a validator, to exercise it at realistic loads.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PacketFilter {
+    /// Only send packets while the validator is the current or an upcoming leader.
+    pub leaders_only: bool,
+    /// Drop simple vote transactions.
+    pub drop_votes: bool,
+    /// Percentage of the remaining packets to send, picked at random.
+    pub sample_percent: u8,
+}
+
+impl Default for PacketFilter {
+    fn default() -> Self {
+        PacketFilter {
+            leaders_only: false,
+            drop_votes: false,
+            sample_percent: 100,
+        }
+    }
+}
+
+impl fmt::Display for PacketFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "leaders_only: {} drop_votes: {} sample_percent: {}",
+            self.leaders_only, self.drop_votes, self.sample_percent
+        )
+    }
+}
+
+impl PacketFilter {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.sample_percent > 100 {
+            return Err(format!(
+                "sample_percent must be at most 100, got {}",
+                self.sample_percent
+            ));
+        }
+        Ok(())
+    }
+
+    /// True if the filter keeps every packet of a batch.
+    pub fn keeps_all_packets(&self) -> bool {
+        !self.drop_votes && self.sample_percent >= 100
+    }
+
+    /// Returns the packets of a batch that pass the vote and sampling filters, or None if none do.
+    /// Leader filtering is up to the caller, which knows the leader schedule.
+    pub fn filter_batch(&self, batch: &PacketBatch) -> Option<PacketBatch> {
+        if self.keeps_all_packets() {
+            return Some(batch.clone());
+        }
+        let mut rng = rand::thread_rng();
+        let packets: Vec<Packet> = batch
+            .packets
+            .iter()
+            .filter(|packet| !(self.drop_votes && is_vote(packet)))
+            .filter(|_| rng.gen_ratio(self.sample_percent as u32, 100))
+            .cloned()
+            .collect();
+        if packets.is_empty() {
+            None
+        } else {
+            Some(PacketBatch { packets })
+        }
+    }
+}
+
+fn is_vote(packet: &Packet) -> bool {
+    packet.flags().map_or(false, |flags| flags.simple_vote_tx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(data: u8, vote: bool) -> Packet {
+        let mut packet = Packet {
+            data: vec![data],
+            ..Packet::default()
+        };
+        packet.flags_mut().simple_vote_tx = vote;
+        packet
+    }
+
+    #[test]
+    fn test_default_keeps_every_packet() {
+        let batch = PacketBatch {
+            packets: vec![packet(0, true), packet(1, false)],
+        };
+        assert_eq!(PacketFilter::default().filter_batch(&batch), Some(batch));
+    }
+
+    #[test]
+    fn test_drop_votes() {
+        let filter = PacketFilter {
+            drop_votes: true,
+            ..PacketFilter::default()
+        };
+        let batch = PacketBatch {
+            packets: vec![packet(0, true), packet(1, false), packet(2, true)],
+        };
+        assert_eq!(
+            filter.filter_batch(&batch),
+            Some(PacketBatch {
+                packets: vec![packet(1, false)]
+            })
+        );
+
+        let votes = PacketBatch {
+            packets: vec![packet(0, true)],
+        };
+        assert_eq!(filter.filter_batch(&votes), None);
+    }
+
+    #[test]
+    fn test_sample_none() {
+        let filter = PacketFilter {
+            sample_percent: 0,
+            ..PacketFilter::default()
+        };
+        let batch = PacketBatch {
+            packets: (0..100).map(|i| packet(i, false)).collect(),
+        };
+        assert_eq!(filter.filter_batch(&batch), None);
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut filter = PacketFilter::default();
+        assert!(filter.validate().is_ok());
+        filter.sample_percent = 101;
+        assert!(filter.validate().is_err());
+    }
+}
@@ -0,0 +1,41 @@
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
+
+/// A validator setting with a default and per-validator overrides.
+#[derive(Clone)]
+pub struct PerValidator<T> {
+    pub default: T,
+    pub overrides: HashMap<Pubkey, T>,
+}
+
+impl<T: Copy> PerValidator<T> {
+    pub fn new(default: T) -> Self {
+        PerValidator {
+            default,
+            overrides: HashMap::default(),
+        }
+    }
+
+    /// The value for a validator, falling back to the default for unknown or anonymous ones.
+    pub fn get(&self, identity: Option<&Pubkey>) -> T {
+        identity
+            .and_then(|identity| self.overrides.get(identity))
+            .copied()
+            .unwrap_or(self.default)
+    }
+
+    /// Sets the value for one validator, or the default if `identity` is None.
+    pub fn set(&mut self, identity: Option<Pubkey>, value: T) {
+        match identity {
+            Some(identity) => {
+                self.overrides.insert(identity, value);
+            }
+            None => self.default = value,
+        }
+    }
+
+    /// Removes a validator's override. Returns false if it had none.
+    pub fn clear(&mut self, identity: &Pubkey) -> bool {
+        self.overrides.remove(identity).is_some()
+    }
+}
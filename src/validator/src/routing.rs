@@ -0,0 +1,32 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Decides which validators receive a bundle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RoutingMode {
+    /// Forward every bundle to every connected validator.
+    Broadcast,
+    /// Forward bundles only to connected validators that are the current or an upcoming leader.
+    LeaderOnly,
+}
+
+impl fmt::Display for RoutingMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RoutingMode::Broadcast => write!(f, "broadcast"),
+            RoutingMode::LeaderOnly => write!(f, "leader_only"),
+        }
+    }
+}
+
+impl FromStr for RoutingMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "broadcast" => Ok(RoutingMode::Broadcast),
+            "leader_only" => Ok(RoutingMode::LeaderOnly),
+            _ => Err(format!("unknown routing mode: {}", s)),
+        }
+    }
+}
@@ -1,3 +1,9 @@
+use crate::backpressure::BackpressurePolicy;
+use crate::forwarder::{Forwarder, SubscriptionStream};
+use crate::routing::RoutingMode;
+use jito_auth::interceptor::authenticated_identity;
+use jito_leader_schedule::tracker::LeaderScheduleTracker;
+use jito_protos::auth::Role;
 use jito_protos::packet::PacketBatch;
 use jito_protos::{
     block_engine::{
@@ -7,198 +13,96 @@ use jito_protos::{
     },
     bundle::BundleUuid,
 };
-use log::{info, warn};
+use log::info;
 use solana_sdk::pubkey::Pubkey;
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
-use std::thread;
-use std::thread::{Builder, JoinHandle};
-use tokio::sync::mpsc::error::TrySendError;
-use tokio::sync::mpsc::{channel, Receiver, Sender};
-use tokio_stream::wrappers::ReceiverStream;
+use tokio::sync::mpsc::Receiver;
+use tokio::sync::watch;
+use tokio::task::JoinError;
 use tonic::{Request, Response, Status};
-use uuid::Uuid;
-
-pub struct AuthInterceptor {}
 
+#[derive(Clone)]
 pub struct ValidatorServerImpl {
-    forwarder_thread: JoinHandle<()>,
-    packet_subscriptions:
-        Arc<Mutex<HashMap<Uuid, Sender<Result<SubscribePacketsResponse, Status>>>>>,
-    bundle_subscriptions:
-        Arc<Mutex<HashMap<Uuid, Sender<Result<SubscribeBundlesResponse, Status>>>>>,
+    forwarder: Forwarder,
+    shutdown_receiver: watch::Receiver<bool>,
 }
 
 impl ValidatorServerImpl {
+    /// Must be called from within a tokio runtime; the forwarder runs as a task on it.
     pub fn new(
         bundle_receiver: Receiver<BundleUuid>,
         packet_receiver: Receiver<PacketBatch>,
+        shutdown_receiver: watch::Receiver<bool>,
+        routing_mode: RoutingMode,
+        leader_schedule: Option<LeaderScheduleTracker>,
+        backpressure_policy: BackpressurePolicy,
+        retry_deliveries: bool,
     ) -> Self {
-        let packet_subscriptions = Arc::new(Mutex::new(HashMap::default()));
-        let bundle_subscriptions = Arc::new(Mutex::new(HashMap::default()));
-        let forwarder_thread = Self::start_forwarder_thread(
+        let forwarder = Forwarder::start(
             bundle_receiver,
             packet_receiver,
-            &packet_subscriptions,
-            &bundle_subscriptions,
+            shutdown_receiver.clone(),
+            routing_mode,
+            leader_schedule,
+            backpressure_policy,
+            retry_deliveries,
         );
         Self {
-            forwarder_thread,
-            packet_subscriptions,
-            bundle_subscriptions,
+            forwarder,
+            shutdown_receiver,
         }
     }
 
-    pub fn join(self) -> thread::Result<()> {
-        self.forwarder_thread.join()
+    /// Waits for the forwarder to drain its channels and exit.
+    pub async fn join(self) -> Result<(), JoinError> {
+        self.forwarder.join().await
     }
 
-    fn start_forwarder_thread(
-        mut bundle_receiver: Receiver<BundleUuid>,
-        mut packet_receiver: Receiver<PacketBatch>,
-        packet_subscriptions: &Arc<
-            Mutex<HashMap<Uuid, Sender<Result<SubscribePacketsResponse, Status>>>>,
-        >,
-        bundle_subscriptions: &Arc<
-            Mutex<HashMap<Uuid, Sender<Result<SubscribeBundlesResponse, Status>>>>,
-        >,
-    ) -> JoinHandle<()> {
-        let packet_subscriptions = packet_subscriptions.clone();
-        let bundle_subscriptions = bundle_subscriptions.clone();
-        Builder::new()
-            .name("forwarder_thread".into())
-            .spawn(move || {
-                let runtime = tokio::runtime::Builder::new_multi_thread()
-                    .enable_all()
-                    .build()
-                    .unwrap();
-                runtime.block_on(async move {
-                    loop {
-                        tokio::select! {
-                            maybe_packet_batch = packet_receiver.recv() => {
-                                if let Some(packet_batch) = maybe_packet_batch {
-                                    let failed_sends = Self::forward_packets(packet_batch, &packet_subscriptions).await;
-                                    for uuid in failed_sends {
-                                        info!("removing packet_subscriptions uuid: {:?}", uuid);
-                                        packet_subscriptions.lock().unwrap().remove(&uuid);
-                                    }
-                                } else {
-                                    warn!("packet_receiver disconnected, exiting");
-                                    break;
-                                }
-                            }
-                            maybe_bundle = bundle_receiver.recv() => {
-                                if let Some(bundle) = maybe_bundle {
-                                    let failed_sends = Self::forward_bundle(bundle, &bundle_subscriptions).await;
-                                    for uuid in failed_sends {
-                                        info!("removing bundle_subscriptions uuid: {:?}", uuid);
-                                        bundle_subscriptions.lock().unwrap().remove(&uuid);
-                                    }
-                                } else {
-                                    warn!("bundle_receiver disconnected, exiting");
-                                    break;
-                                }
-                            }
-                        }
-                    }
-                })
-            })
-            .unwrap()
+    pub fn forwarder(&self) -> &Forwarder {
+        &self.forwarder
     }
 
-    async fn forward_packets(
-        packet_batch: PacketBatch,
-        packet_subscriptions: &Arc<
-            Mutex<HashMap<Uuid, Sender<Result<SubscribePacketsResponse, Status>>>>,
-        >,
-    ) -> Vec<Uuid> {
-        let mut failed_sends = Vec::new();
-        let subs = packet_subscriptions.lock().unwrap();
-        for (uuid, sender) in subs.iter() {
-            match sender.try_send(Ok(SubscribePacketsResponse {
-                header: None,
-                batch: Some(packet_batch.clone()),
-            })) {
-                Ok(_) => {}
-                Err(TrySendError::Closed(_)) => {
-                    failed_sends.push(*uuid);
-                }
-                Err(TrySendError::Full(_)) => {
-                    warn!("packet channel full uuid: {:?}", uuid);
-                }
-            }
+    fn check_not_shutting_down(&self) -> Result<(), Status> {
+        if *self.shutdown_receiver.borrow() {
+            return Err(Status::unavailable("block engine shutting down"));
         }
-        failed_sends
+        Ok(())
     }
 
-    async fn forward_bundle(
-        bundle: BundleUuid,
-        bundle_subscriptions: &Arc<
-            Mutex<HashMap<Uuid, Sender<Result<SubscribeBundlesResponse, Status>>>>,
-        >,
-    ) -> Vec<Uuid> {
-        let mut failed_sends = Vec::new();
-        let subs = bundle_subscriptions.lock().unwrap();
-        for (uuid, sender) in subs.iter() {
-            match sender.try_send(Ok(SubscribeBundlesResponse {
-                bundles: vec![bundle.clone()],
-            })) {
-                Ok(_) => {
-                    info!("bundle forwarded validator uuid: {:?}", uuid);
-                }
-                Err(TrySendError::Closed(_)) => {
-                    warn!("bundle channel closed validator uuid: {:?}", uuid);
-                    failed_sends.push(*uuid);
-                }
-                Err(TrySendError::Full(_)) => {
-                    warn!("bundle channel full validator uuid: {:?}", uuid);
-                }
+    /// Returns the authenticated validator identity, if any. Other roles may not subscribe.
+    fn validator_identity<T>(request: &Request<T>) -> Result<Option<Pubkey>, Status> {
+        match authenticated_identity(request) {
+            Some(identity) if identity.role != Role::Validator => {
+                Err(Status::permission_denied("only validators may subscribe"))
             }
+            identity => Ok(identity.map(|identity| identity.pubkey)),
         }
-        failed_sends
     }
 }
 
 #[tonic::async_trait]
 impl BlockEngineValidator for ValidatorServerImpl {
-    type SubscribePacketsStream = ReceiverStream<Result<SubscribePacketsResponse, Status>>;
+    type SubscribePacketsStream = SubscriptionStream<SubscribePacketsResponse>;
 
     async fn subscribe_packets(
         &self,
-        _request: Request<SubscribePacketsRequest>,
+        request: Request<SubscribePacketsRequest>,
     ) -> Result<Response<Self::SubscribePacketsStream>, Status> {
-        let (sender, receiver) = channel(1000);
-
-        let uuid = Uuid::new_v4();
-
-        info!("adding packet_subscriptions uuid: {:?}", uuid);
+        self.check_not_shutting_down()?;
+        let identity = Self::validator_identity(&request)?;
 
-        self.packet_subscriptions
-            .lock()
-            .unwrap()
-            .insert(uuid, sender);
-
-        Ok(Response::new(ReceiverStream::new(receiver)))
+        Ok(Response::new(self.forwarder.subscribe_packets(identity)?))
     }
 
-    type SubscribeBundlesStream = ReceiverStream<Result<SubscribeBundlesResponse, Status>>;
+    type SubscribeBundlesStream = SubscriptionStream<SubscribeBundlesResponse>;
 
     async fn subscribe_bundles(
         &self,
-        _request: Request<SubscribeBundlesRequest>,
+        request: Request<SubscribeBundlesRequest>,
     ) -> Result<Response<Self::SubscribeBundlesStream>, Status> {
-        let (sender, receiver) = channel(1000);
-
-        let uuid = Uuid::new_v4();
-
-        info!("adding bundle_subscriptions uuid: {:?}", uuid);
-
-        self.bundle_subscriptions
-            .lock()
-            .unwrap()
-            .insert(uuid, sender);
+        self.check_not_shutting_down()?;
+        let identity = Self::validator_identity(&request)?;
 
-        Ok(Response::new(ReceiverStream::new(receiver)))
+        Ok(Response::new(self.forwarder.subscribe_bundles(identity)?))
     }
 
     async fn get_block_builder_fee_info(